@@ -0,0 +1,7 @@
+// The background page lives at the extension root, so chunks are not found
+// relative to it.
+__webpack_public_path__ = "/dist/";
+
+import("../rust/pkg").then(module => {
+  module.run_background();
+});
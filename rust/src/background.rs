@@ -0,0 +1,181 @@
+use fetch;
+use futures::Future;
+use js_sys::{Date, Function};
+use notification;
+use serde::Serialize;
+use serde_json::json;
+use squark::{App, Diff, Env, Runtime, Task, View};
+use squark_macros::view;
+use state::{Feed, State};
+use store;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::future_to_promise;
+use {chrome, fetch_feed, timeout, Action, WinoApp};
+
+/// Sent by the page to have every feed fetched now.
+pub const RELOAD_MESSAGE: &str = "reload";
+
+const BADGE_COLOR: &str = "#3273dc";
+const BADGE_ERROR_COLOR: &str = "#ff3860";
+const BADGE_UPDATING_COLOR: &str = "#7a7a7a";
+
+/// Polls feeds from the extension's background page, so they stay fresh
+/// while no wino tab is open. Pages reflect what it stores through
+/// `Action::Sync`.
+#[derive(Clone, Debug, Default)]
+struct BackgroundApp;
+
+impl App for BackgroundApp {
+    type State = State;
+    type Action = Action;
+
+    fn reducer(&self, state: State, action: Action) -> (State, Task<Action>) {
+        let old_state = state.clone();
+        let is_sync = match action {
+            Action::Sync(_) => true,
+            _ => false,
+        };
+
+        let (state, mut task) = match action {
+            Action::Reload => {
+                let mut task = Task::empty();
+                for feed in state.feed_map.values().filter(|f| !f.status.dead) {
+                    task.push(Box::new(fetch_feed(feed)));
+                }
+                (state, task)
+            }
+            action => WinoApp._reducer(state, action),
+        };
+
+        reschedule(&old_state, &state, &mut task);
+        update_badge(&state);
+        if state != old_state && !is_sync {
+            notification::notify_new_articles(&old_state, &state);
+            store::save(&old_state, &state);
+        }
+
+        (state, task)
+    }
+
+    fn view(&self, _: State) -> View<Action> {
+        view! { <div></div> }
+    }
+}
+
+/// Starts timers for feeds whose next fetch moved, whether a fetch here or
+/// a page changed it, and drops fetches of feeds which went away.
+fn reschedule(old_state: &State, state: &State, task: &mut Task<Action>) {
+    for (url, feed) in &state.feed_map {
+        let next_fetch = old_state.feed_map.get(url).map(|f| f.status.next_fetch);
+        if !feed.status.dead && next_fetch != Some(feed.status.next_fetch) {
+            task.push(Box::new(fetch_when_due(feed)));
+        }
+    }
+    for url in old_state.feed_map.keys() {
+        if !state.feed_map.contains_key(url) {
+            fetch::cancel(url);
+        }
+    }
+    if state.fetch_concurrency != old_state.fetch_concurrency {
+        fetch::set_concurrency(state.fetch_concurrency);
+    }
+}
+
+/// Shows the unread count on the toolbar button, red while any feed is
+/// failing and grey while fetches are under way.
+fn update_badge(state: &State) {
+    let text = match state.unread_count() {
+        0 => "".to_string(),
+        count if count > 999 => "999+".to_string(),
+        count => count.to_string(),
+    };
+    let is_failing = state
+        .feed_map
+        .values()
+        .any(|f| f.status.last_error.is_some());
+    let color = if is_failing {
+        BADGE_ERROR_COLOR
+    } else if fetch::pending() > 0 {
+        BADGE_UPDATING_COLOR
+    } else {
+        BADGE_COLOR
+    };
+
+    let browser_action = chrome.browser_action();
+    browser_action.set_badge_text(&JsValue::from_serde(&json!({ "text": text })).unwrap());
+    browser_action
+        .set_badge_background_color(&JsValue::from_serde(&json!({ "color": color })).unwrap());
+}
+
+/// Dispatches `Action::FetchDue` once the feed's next fetch comes.
+pub fn fetch_when_due(feed: &Feed) -> impl Future<Item = Action, Error = ()> {
+    // Rounded up, as a timer firing short of `next_fetch` finds it not due.
+    let delay = (feed.status.next_fetch - Date::now()).max(0.0).ceil();
+    let action = Action::FetchDue(feed.url.clone(), feed.status.next_fetch);
+    timeout(action, delay as i32)
+}
+
+/// Runs `BackgroundApp` without rendering anything.
+#[derive(Clone)]
+struct BackgroundRuntime {
+    env: Env<BackgroundApp>,
+}
+
+impl Runtime<BackgroundApp> for BackgroundRuntime {
+    fn get_env<'a>(&'a self) -> &'a Env<BackgroundApp> {
+        &self.env
+    }
+
+    fn handle_diff(&self, _: Diff) {}
+
+    fn handle_future<T: Serialize + 'static, E: Serialize + 'static>(
+        &self,
+        future: Box<Future<Item = T, Error = E>>,
+    ) {
+        future_to_promise(future.map(|_| JsValue::null()).map_err(|_| JsValue::null()));
+    }
+
+    fn schedule_render(&self) {}
+}
+
+pub fn run() {
+    let future = store::load().map(|state| {
+        start(state);
+        JsValue::null()
+    });
+    future_to_promise(future.map_err(|_| JsValue::null()));
+}
+
+fn start(state: State) {
+    fetch::set_concurrency(state.fetch_concurrency);
+    update_badge(&state);
+    let mut task = Task::empty();
+    for feed in state.feed_map.values().filter(|f| !f.status.dead) {
+        task.push(Box::new(fetch_when_due(feed)));
+    }
+
+    let runtime = BackgroundRuntime {
+        env: Env::new(state),
+    };
+
+    let runtime_1 = runtime.clone();
+    store::listen(move |change| runtime_1.on_action(Action::Sync(change)));
+
+    let runtime_2 = runtime.clone();
+    let closure = Closure::wrap(Box::new(move |message: JsValue| {
+        if message.as_string().as_ref().map(String::as_str) == Some(RELOAD_MESSAGE) {
+            runtime_2.on_action(Action::Reload);
+        }
+    }) as Box<FnMut(_)>);
+    chrome
+        .runtime()
+        .on_message()
+        .add_listener(closure.as_ref().unchecked_ref::<Function>());
+    closure.forget();
+
+    let runtime_3 = runtime.clone();
+    notification::listen(move |action| runtime_3.on_action(action));
+
+    runtime.run_with_task(task);
+}
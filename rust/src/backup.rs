@@ -0,0 +1,366 @@
+use bincode;
+use date;
+use futures::future::{self, Either};
+use futures::Future;
+use js_sys::{Array, Promise, Uint8Array};
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+use state::{Feed, State};
+use std::collections::HashMap;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Blob;
+
+/// Schema version written by `export`.
+pub const VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` backup to version `n + 2`.
+const MIGRATIONS: &[fn(Value) -> Result<Value, Error>] = &[];
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+#[wasm_bindgen]
+extern "C" {
+    type ReadableStream;
+    #[wasm_bindgen(method, js_name = pipeThrough)]
+    fn pipe_through(this: &ReadableStream, transform: &JsValue) -> ReadableStream;
+
+    type CompressionStream;
+    #[wasm_bindgen(constructor)]
+    fn new(format: &str) -> CompressionStream;
+
+    type DecompressionStream;
+    #[wasm_bindgen(constructor)]
+    fn new(format: &str) -> DecompressionStream;
+
+    #[wasm_bindgen(js_name = Blob)]
+    type StreamBlob;
+    #[wasm_bindgen(method, js_class = "Blob")]
+    fn stream(this: &StreamBlob) -> ReadableStream;
+
+    #[wasm_bindgen(js_name = Response)]
+    type StreamResponse;
+    #[wasm_bindgen(constructor, js_class = "Response")]
+    fn new(body: &ReadableStream) -> StreamResponse;
+    #[wasm_bindgen(method, js_class = "Response", js_name = arrayBuffer)]
+    fn array_buffer(this: &StreamResponse) -> Promise;
+}
+
+#[derive(Deserialize, Serialize)]
+struct Backup {
+    version: u32,
+    exported_at: String,
+    feeds: Vec<Feed>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    MissingVersion,
+    /// A version below 1, which no JSON backup was ever written with.
+    InvalidVersion(u64),
+    /// A version newer than `VERSION`.
+    UnsupportedVersion(u64),
+    UnknownFormat,
+    /// The gzip stream could not be inflated.
+    Corrupt,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "invalid backup: {}", e),
+            Error::MissingVersion => write!(f, "backup has no schema version"),
+            Error::InvalidVersion(version) => write!(
+                f,
+                "backup version {} is not a valid schema version",
+                version
+            ),
+            Error::UnsupportedVersion(version) => write!(
+                f,
+                "backup version {} is newer than this wino supports ({})",
+                version, VERSION
+            ),
+            Error::UnknownFormat => write!(f, "not a wino backup"),
+            Error::Corrupt => write!(f, "backup is truncated or corrupt"),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+/// What `restore` got out of a backup.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Restored {
+    pub feed_map: HashMap<String, Feed>,
+    pub report: Report,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub version: u32,
+    pub feeds: usize,
+    pub articles: usize,
+    pub skipped: Vec<String>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Restored {} feeds and {} articles from a version {} backup.",
+            self.feeds, self.articles, self.version
+        )?;
+        if !self.skipped.is_empty() {
+            write!(f, " Skipped {}.", self.skipped.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A restored backup waiting for the user to pick how to apply it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingImport {
+    pub restored: Restored,
+    pub summary: Summary,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+/// How an import would change the current subscriptions, shown before it
+/// is committed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub conflicting: Vec<String>,
+}
+
+impl Summary {
+    pub fn new(local: &HashMap<String, Feed>, imported: &HashMap<String, Feed>) -> Summary {
+        let mut summary = Summary::default();
+        for (url, feed) in imported {
+            let title = feed.display_title().to_string();
+            let local_feed = match local.get(url) {
+                Some(local_feed) => local_feed,
+                None => {
+                    summary.added.push(title);
+                    continue;
+                }
+            };
+            if local_feed.conflicts_with(feed) {
+                summary.conflicting.push(title.clone());
+            }
+            let mut merged = local_feed.clone();
+            merged.merge_copy(feed.clone());
+            if &merged != local_feed {
+                summary.updated.push(title);
+            }
+        }
+        summary.added.sort();
+        summary.updated.sort();
+        summary.conflicting.sort();
+        summary
+    }
+}
+
+/// Unions `imported` into `local`; see `Feed::merge_copy`.
+pub fn merge(local: &mut HashMap<String, Feed>, imported: HashMap<String, Feed>) {
+    for (url, feed) in imported {
+        match local.get_mut(&url) {
+            Some(local_feed) => local_feed.merge_copy(feed),
+            None => {
+                local.insert(url, feed);
+            }
+        }
+    }
+}
+
+/// Serializes the subscriptions and their articles as pretty-printed JSON.
+///
+/// Object keys come out sorted, so two backups can be diffed.
+pub fn export(state: &State) -> String {
+    let mut feeds: Vec<Feed> = state.feed_map.values().cloned().collect();
+    feeds.sort_by(|a, b| a.url.cmp(&b.url));
+    let backup = Backup {
+        version: VERSION,
+        exported_at: date::now().to_rfc3339(),
+        feeds,
+    };
+    let value = serde_json::to_value(&backup).unwrap();
+    serde_json::to_string_pretty(&value).unwrap()
+}
+
+pub fn file_name(compressed: bool) -> String {
+    let name = format!("wino-backup-{}.json", date::now().format("%Y-%m-%d"));
+    if compressed {
+        return format!("{}.gz", name);
+    }
+    name
+}
+
+/// Reads a JSON backup of any known version, or a bincode export from
+/// before versioned backups existed.
+pub fn restore(buf: &[u8]) -> Result<Restored, Error> {
+    let is_json = buf
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map_or(false, |b| *b == b'{');
+    if !is_json {
+        let state: legacy::State = bincode::deserialize(buf).map_err(|_| Error::UnknownFormat)?;
+        let feeds = state.feed_map.into_iter().map(|(_, f)| f.into()).collect();
+        return Ok(validate(0, feeds));
+    }
+
+    let mut value: Value = serde_json::from_slice(buf)?;
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(Error::MissingVersion)?;
+    if version == 0 {
+        return Err(Error::InvalidVersion(version));
+    }
+    if version > u64::from(VERSION) {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    // In range, so this cannot truncate.
+    let version = version as u32;
+    for migrate in &MIGRATIONS[(version - 1) as usize..] {
+        value = migrate(value)?;
+    }
+
+    let backup: Backup = serde_json::from_value(value)?;
+    Ok(validate(version, backup.feeds))
+}
+
+fn validate(version: u32, feeds: Vec<Feed>) -> Restored {
+    let mut feed_map = HashMap::new();
+    let mut report = Report {
+        version,
+        feeds: 0,
+        articles: 0,
+        skipped: vec![],
+    };
+    for feed in feeds {
+        if !feed.url.starts_with("http://") && !feed.url.starts_with("https://") {
+            report
+                .skipped
+                .push(format!("\"{}\" (invalid URL)", feed.display_title()));
+            continue;
+        }
+        if feed_map.contains_key(&feed.url) {
+            report.skipped.push(format!("{} (duplicate)", feed.url));
+            continue;
+        }
+        report.feeds += 1;
+        report.articles += feed.article_map.len();
+        feed_map.insert(feed.url.clone(), feed);
+    }
+
+    Restored { feed_map, report }
+}
+
+pub fn compress(buf: Vec<u8>) -> impl Future<Item = Vec<u8>, Error = ()> {
+    pipe_through(&buf, &CompressionStream::new("gzip"))
+        .map_err(|e| panic!("compression errored; err={:?}", e))
+}
+
+/// Inflates `buf` if it is gzipped, and passes it through otherwise.
+pub fn decompress(buf: Vec<u8>) -> impl Future<Item = Vec<u8>, Error = Error> {
+    if !buf.starts_with(GZIP_MAGIC) {
+        return Either::A(future::ok(buf));
+    }
+    Either::B(pipe_through(&buf, &DecompressionStream::new("gzip")).map_err(|_| Error::Corrupt))
+}
+
+fn pipe_through(buf: &[u8], transform: &JsValue) -> impl Future<Item = Vec<u8>, Error = JsValue> {
+    let b = Uint8Array::new(&unsafe { Uint8Array::view(buf) }.into());
+    let array = Array::new();
+    array.push(&b.buffer());
+    let blob = Blob::new_with_u8_array_sequence(&array).unwrap();
+    let stream = blob
+        .unchecked_ref::<StreamBlob>()
+        .stream()
+        .pipe_through(transform);
+    JsFuture::from(StreamResponse::new(&stream).array_buffer()).map(|array| {
+        let array = Uint8Array::new(&array);
+        let mut buf = vec![0; array.length() as usize];
+        array.copy_to(&mut buf);
+        buf
+    })
+}
+
+/// The `State` layout bincode exports were written with.
+mod legacy {
+    use chrono::{DateTime, FixedOffset};
+    use serde::Deserialize;
+    use state::{self, DateSource};
+    use std::collections::HashMap;
+
+    // Only `feed_map` is restored, but bincode needs every field.
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    pub struct State {
+        pub new_feed_url: String,
+        pub is_loading_new_feed: bool,
+        pub is_opening_sidebar: bool,
+        pub feed_map: HashMap<String, Feed>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Feed {
+        pub title: String,
+        pub url: String,
+        pub article_map: HashMap<String, Article>,
+        pub updated: f64,
+        pub visible: bool,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Article {
+        pub title: String,
+        pub date: DateTime<FixedOffset>,
+        pub url: String,
+    }
+
+    impl From<Feed> for state::Feed {
+        fn from(feed: Feed) -> state::Feed {
+            let updated = feed.updated;
+            let article_map = feed
+                .article_map
+                .into_iter()
+                .map(|(id, a)| {
+                    let article = state::Article {
+                        title: a.title,
+                        date: a.date,
+                        date_source: DateSource::Published,
+                        url: a.url,
+                        first_seen: updated,
+                        last_seen: updated,
+                        read: false,
+                        starred: false,
+                    };
+                    (id, article)
+                })
+                .collect();
+            state::Feed {
+                title: feed.title,
+                url: feed.url,
+                article_map,
+                updated,
+                visible: feed.visible,
+                ..Default::default()
+            }
+        }
+    }
+}
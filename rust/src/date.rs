@@ -0,0 +1,199 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use js_sys::Date;
+
+const ZONED_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%Y-%m-%dT%H:%M%z",
+    "%Y-%m-%d %H:%M:%S%.f %z",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M %z",
+    "%Y/%m/%d %H:%M:%S %z",
+    "%d %B %Y %H:%M:%S %z",
+    "%d %B %Y %H:%M %z",
+    "%B %d %Y %H:%M:%S %z",
+    "%B %d %Y %H:%M %z",
+    "%a %b %d %H:%M:%S %z %Y",
+];
+
+const NAIVE_DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d %H:%M",
+    "%Y.%m.%d %H:%M:%S",
+    "%Y.%m.%d %H:%M",
+    "%Y年%m月%d日 %H:%M:%S",
+    "%Y年%m月%d日 %H:%M",
+    "%Y年%m月%d日 %H時%M分",
+    "%d %B %Y %H:%M:%S",
+    "%d %B %Y %H:%M",
+    "%B %d %Y %H:%M:%S",
+    "%B %d %Y %H:%M",
+];
+
+const DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%Y.%m.%d",
+    "%Y年%m月%d日",
+    "%d %B %Y",
+    "%B %d %Y",
+];
+
+// Zone abbreviations seen in the wild which `%z` doesn't understand.
+const ZONE_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("Z", "+0000"),
+    ("UT", "+0000"),
+    ("UTC", "+0000"),
+    ("GMT", "+0000"),
+    ("JST", "+0900"),
+    ("KST", "+0900"),
+    ("CET", "+0100"),
+    ("CEST", "+0200"),
+    ("EST", "-0500"),
+    ("EDT", "-0400"),
+    ("CST", "-0600"),
+    ("CDT", "-0500"),
+    ("MST", "-0700"),
+    ("MDT", "-0600"),
+    ("PST", "-0800"),
+    ("PDT", "-0700"),
+];
+
+/// Parses a feed date as leniently as possible.
+///
+/// Dates without a timezone are taken as UTC.
+pub fn parse(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(s).or_else(|_| DateTime::parse_from_rfc2822(s)) {
+        return Some(date);
+    }
+
+    let s = normalize(s);
+    if let Ok(date) = DateTime::parse_from_rfc2822(&s) {
+        return Some(date);
+    }
+
+    for format in ZONED_FORMATS {
+        if let Ok(date) = DateTime::parse_from_str(&s, format) {
+            return Some(date);
+        }
+    }
+
+    let utc = FixedOffset::east(0);
+    for format in NAIVE_DATETIME_FORMATS {
+        if let Ok(date) = NaiveDateTime::parse_from_str(&s, format) {
+            return utc.from_local_datetime(&date).single();
+        }
+    }
+
+    for format in DATE_FORMATS {
+        if let Ok(date) = NaiveDate::parse_from_str(&s, format) {
+            return utc.from_local_datetime(&date.and_hms(0, 0, 0)).single();
+        }
+    }
+
+    None
+}
+
+pub fn now() -> DateTime<FixedOffset> {
+    FixedOffset::east(0).timestamp_millis(Date::now() as i64)
+}
+
+/// Strips the decorations that keep otherwise well-formed dates from
+/// parsing: leading day names in any language, parenthesized day names,
+/// ordinal suffixes, commas and zone abbreviations.
+fn normalize(s: &str) -> String {
+    let mut s = s.to_string();
+
+    if let Some(i) = s.find(',') {
+        if !s[..i].chars().any(|c| c.is_ascii_digit()) {
+            s = s[i + 1..].to_string();
+        }
+    }
+
+    let mut words: Vec<String> = s
+        .replace(',', " ")
+        .split_whitespace()
+        .map(|word| strip_day_name(&strip_ordinal(word)))
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if let Some(last) = words.pop() {
+        let zone = ZONE_ABBREVIATIONS
+            .iter()
+            .find(|(abbr, _)| abbr.eq_ignore_ascii_case(&last))
+            .map(|(_, offset)| offset.to_string());
+        words.push(zone.unwrap_or(last));
+    }
+
+    words.join(" ")
+}
+
+fn strip_ordinal(word: &str) -> String {
+    let digits = word.chars().take_while(char::is_ascii_digit).count();
+    let suffix = &word[digits..];
+    if digits > 0 && ["st", "nd", "rd", "th"].contains(&suffix) {
+        return word[..digits].to_string();
+    }
+    word.to_string()
+}
+
+// "2019年3月1日(金)" or "(Fri)"
+fn strip_day_name(word: &str) -> String {
+    match (word.find('('), word.rfind(')')) {
+        (Some(start), Some(end)) if start < end => {
+            let inner = &word[start + 1..end];
+            if inner.chars().any(|c| c.is_ascii_digit()) {
+                return word.to_string();
+            }
+            format!("{}{}", &word[..start], &word[end + 1..])
+        }
+        _ => word.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> Option<DateTime<FixedOffset>> {
+        Some(DateTime::parse_from_rfc3339(s).unwrap())
+    }
+
+    #[test]
+    fn parse_lenient_dates() {
+        let cases = &[
+            (
+                "Fri, 01 Mar 2019 12:00:00 +0900",
+                "2019-03-01T12:00:00+09:00",
+            ),
+            ("Fri, 01 Mar 2019 12:00:00 JST", "2019-03-01T12:00:00+09:00"),
+            ("2019-03-01T12:00:00Z", "2019-03-01T12:00:00Z"),
+            ("2019-03-01 12:00", "2019-03-01T12:00:00Z"),
+            ("2019年3月1日(金) 12:00", "2019-03-01T12:00:00Z"),
+            ("March 1st, 2019", "2019-03-01T00:00:00Z"),
+            (
+                "Friday, March 1st, 2019 12:00 PST",
+                "2019-03-01T12:00:00-08:00",
+            ),
+            ("2019/03/01", "2019-03-01T00:00:00Z"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input), at(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_empty_and_garbage() {
+        for input in &["", "   ", "yesterday", "not a date", "2019-13-45"] {
+            assert_eq!(parse(input), None, "{}", input);
+        }
+    }
+}
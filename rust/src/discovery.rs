@@ -0,0 +1,88 @@
+use fetch;
+use futures::future::{self, Future};
+use state::Feed;
+use wasm_bindgen::JsCast;
+use web_sys::{DomParser, Element, SupportedType, Url};
+
+const FEED_TYPES: &[(&str, &str)] = &[
+    ("application/rss+xml", "RSS"),
+    ("application/atom+xml", "Atom"),
+    ("application/feed+json", "JSON Feed"),
+];
+
+/// Tried when a page advertises no feeds.
+const COMMON_PATHS: &[&str] = &["/feed", "/rss.xml", "/atom.xml"];
+
+/// A feed found from a website URL, offered before subscribing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub url: String,
+    pub title: String,
+}
+
+pub fn is_html(content_type: Option<&str>, body: &str) -> bool {
+    if let Some(content_type) = content_type {
+        return content_type.contains("html");
+    }
+    let head = body.trim_start().get(..15).unwrap_or("").to_lowercase();
+    head.starts_with("<!doctype html") || head.starts_with("<html")
+}
+
+/// Collects the feeds `body` links with `<link rel="alternate">`, resolving
+/// their URLs against `page_url`.
+pub fn discover(page_url: &str, body: &str) -> Vec<Candidate> {
+    let document = DomParser::new()
+        .unwrap()
+        .parse_from_string(body, SupportedType::TextHtml)
+        .unwrap();
+    let links = document
+        .query_selector_all("link[rel~=alternate][href]")
+        .unwrap();
+
+    let mut candidates: Vec<Candidate> = vec![];
+    for i in 0..links.length() {
+        let link: Element = links.get(i).unwrap().unchecked_into();
+        let type_ = link.get_attribute("type").unwrap_or_default();
+        let label = match FEED_TYPES.iter().find(|(t, _)| type_.starts_with(t)) {
+            Some((_, label)) => label,
+            None => continue,
+        };
+        let href = link.get_attribute("href").unwrap();
+        let url = match Url::new_with_base(&href, page_url) {
+            Ok(url) => url.href(),
+            Err(_) => continue,
+        };
+        if candidates.iter().any(|c| c.url == url) {
+            continue;
+        }
+        let title = match link.get_attribute("title") {
+            Some(ref title) if !title.is_empty() => format!("{} ({})", title, label),
+            _ => label.to_string(),
+        };
+        candidates.push(Candidate { url, title });
+    }
+    candidates
+}
+
+/// Fetches `COMMON_PATHS` on the page's origin and keeps whichever parse as
+/// feeds.
+pub fn probe(page_url: &str) -> impl Future<Item = Vec<Candidate>, Error = ()> {
+    let urls: Vec<String> = COMMON_PATHS
+        .iter()
+        .filter_map(|path| Url::new_with_base(path, page_url).ok())
+        .map(|url| url.href())
+        .collect();
+    let futures = urls.into_iter().map(|url| {
+        fetch::get(&url).then(move |result| {
+            let candidate = result.ok().and_then(|resp| {
+                let feed = Feed::parse(url.clone(), resp.content_type(), &resp.body).ok()?;
+                Some(Candidate {
+                    title: feed.display_title().to_string(),
+                    url,
+                })
+            });
+            Ok(candidate)
+        })
+    });
+    future::join_all(futures).map(|candidates| candidates.into_iter().filter_map(|c| c).collect())
+}
@@ -1,26 +1,352 @@
+use date;
 use futures::Future;
-use wasm_bindgen::{JsCast, JsValue};
+use js_sys::{self, Array, Function, Promise};
+use state::FeedError;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use web_sys::{
+    AbortController, AbortSignal, Headers, Request, RequestInit, RequestMode,
+    Response as WebResponse, Url,
+};
 
-pub(crate) fn fetch(request: Request) -> impl Future<Item = JsValue, Error = JsValue> {
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Error {
+    Network,
+    Timeout,
+    Cancelled,
+    NotFound,
+    Gone,
+    /// 429, with the `Retry-After` delay in seconds when one was given.
+    RateLimited(Option<u32>),
+    Server(u16),
+    Status(u16),
+}
+
+impl Error {
+    fn from_response(resp: &WebResponse) -> Error {
+        match resp.status() {
+            404 => Error::NotFound,
+            410 => Error::Gone,
+            429 => {
+                let retry_after = resp.headers().get("Retry-After").unwrap();
+                Error::RateLimited(retry_after.and_then(|s| parse_retry_after(&s)))
+            }
+            status if status >= 500 => Error::Server(status),
+            status => Error::Status(status),
+        }
+    }
+}
+
+impl From<Error> for FeedError {
+    fn from(e: Error) -> FeedError {
+        match e {
+            // Cancelled fetches belong to removed feeds and are dropped
+            // before reaching the state.
+            Error::Network | Error::Cancelled => FeedError::Network,
+            Error::Timeout => FeedError::Timeout,
+            Error::NotFound => FeedError::NotFound,
+            Error::Gone => FeedError::Gone,
+            Error::RateLimited(retry_after) => FeedError::RateLimited(retry_after),
+            Error::Server(status) => FeedError::ServerError(status),
+            Error::Status(status) => FeedError::HttpStatus(status),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Response {
+    pub status: u16,
+    /// Where the response came from after following redirects.
+    pub url: String,
+    pub redirected: bool,
+    /// Keyed by lowercase header name.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type")
+    }
+
+    pub fn is_not_modified(&self) -> bool {
+        self.status == 304
+    }
+}
+
+/// Resolves to the response of any 2xx or 304 status; other statuses fail
+/// without reading the body.
+pub(crate) fn fetch(request: Request) -> impl Future<Item = Response, Error = Error> {
     let window = web_sys::window().unwrap();
     let request_promise = window.fetch_with_request(&request);
 
     JsFuture::from(request_promise)
+        .map_err(|_| Error::Network)
         .and_then(|resp_value| {
-            let resp: Response = resp_value.dyn_into().unwrap();
-            resp.text()
+            let resp: WebResponse = resp_value.dyn_into().unwrap();
+            if !resp.ok() && resp.status() != 304 {
+                return Err(Error::from_response(&resp));
+            }
+            let response = Response {
+                status: resp.status(),
+                url: resp.url(),
+                redirected: resp.redirected(),
+                headers: collect_headers(&resp.headers()),
+                body: String::new(),
+            };
+            let text = resp.text().map_err(|_| Error::Network)?;
+            Ok((response, text))
+        })
+        .and_then(|(response, p)| {
+            JsFuture::from(p)
+                .map_err(|_| Error::Network)
+                .map(|body| Response {
+                    body: body.as_string().unwrap(),
+                    ..response
+                })
         })
-        .and_then(JsFuture::from)
 }
 
-pub(crate) fn get(url: &str) -> impl Future<Item = JsValue, Error = JsValue> {
+fn collect_headers(headers: &Headers) -> HashMap<String, String> {
+    let mut header_map = HashMap::new();
+    let entries = match js_sys::try_iter(headers) {
+        Ok(Some(entries)) => entries,
+        _ => return header_map,
+    };
+    for entry in entries {
+        let entry: Array = entry.unwrap().unchecked_into();
+        let (name, value) = (entry.shift().as_string(), entry.shift().as_string());
+        if let (Some(name), Some(value)) = (name, value) {
+            header_map.insert(name.to_lowercase(), value);
+        }
+    }
+    header_map
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(s: &str) -> Option<u32> {
+    if let Ok(seconds) = s.trim().parse() {
+        return Some(seconds);
+    }
+    let seconds = (date::parse(s)? - date::now()).num_seconds();
+    Some(seconds.max(0) as u32)
+}
+
+fn get_request(url: &str, signal: Option<&AbortSignal>) -> Request {
     let mut opts = RequestInit::new();
     opts.method("GET");
     opts.mode(RequestMode::Cors);
+    opts.signal(signal);
+
+    Request::new_with_str_and_init(url, &opts).unwrap()
+}
+
+pub(crate) fn get(url: &str) -> impl Future<Item = Response, Error = Error> {
+    fetch(get_request(url, None))
+}
+
+/// Validators of an earlier response, sent back so an unchanged resource
+/// comes back as an empty 304.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Fetches `url` once the queue has room for it, both overall and on its
+/// origin. Everything queued under `key` can be dropped with `cancel`.
+pub(crate) fn queued_get(
+    key: &str,
+    url: &str,
+    validators: Validators,
+) -> impl Future<Item = Response, Error = Error> {
+    let url = url.to_string();
+    let origin = Url::new(&url).map(|u| u.origin()).unwrap_or_default();
+    let id = QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        queue.next_id += 1;
+        queue.next_id
+    });
+    let key = key.to_string();
+    let start = Promise::new(&mut |start, cancel| {
+        let waiting = Waiting {
+            id,
+            key: key.clone(),
+            origin: origin.clone(),
+            start,
+            cancel,
+        };
+        QUEUE.with(|queue| queue.borrow_mut().waiting.push_back(waiting));
+    });
+    pump();
+
+    JsFuture::from(start)
+        .map_err(|_| Error::Cancelled)
+        .and_then(move |controller| {
+            let controller: AbortController = controller.unchecked_into();
+            abort_after(id, TIMEOUT_MS);
+            let request = get_request(&url, Some(&controller.signal()));
+            let headers = request.headers();
+            if let Some(ref etag) = validators.etag {
+                headers.set("If-None-Match", etag).unwrap();
+            }
+            if let Some(ref last_modified) = validators.last_modified {
+                headers.set("If-Modified-Since", last_modified).unwrap();
+            }
+            fetch(request)
+        })
+        .then(move |result| {
+            let abort_reason = finish(id);
+            match (result, abort_reason) {
+                (Err(_), Some(reason)) => Err(reason),
+                (result, _) => result,
+            }
+        })
+}
+
+/// Drops every queued and running request made under `key`.
+pub(crate) fn cancel(key: &str) {
+    let cancelled = QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        for running in queue.running.values_mut().filter(|r| r.key == key) {
+            running.abort(Error::Cancelled);
+        }
+        let cancelled: Vec<Function> = queue
+            .waiting
+            .iter()
+            .filter(|w| w.key == key)
+            .map(|w| w.cancel.clone())
+            .collect();
+        queue.waiting.retain(|w| w.key != key);
+        cancelled
+    });
+    for cancel in cancelled {
+        cancel.call0(&JsValue::null()).unwrap();
+    }
+}
+
+/// Requests queued or in flight.
+pub(crate) fn pending() -> usize {
+    QUEUE.with(|queue| {
+        let queue = queue.borrow();
+        queue.waiting.len() + queue.running.len()
+    })
+}
 
-    let request = Request::new_with_str_and_init(url, &opts).unwrap();
+pub(crate) fn set_concurrency(concurrency: usize) {
+    QUEUE.with(|queue| queue.borrow_mut().concurrency = concurrency.max(1));
+    pump();
+}
+
+pub(crate) const DEFAULT_CONCURRENCY: usize = 6;
+const CONCURRENCY_PER_ORIGIN: usize = 2;
+const TIMEOUT_MS: i32 = 30 * 1000;
+
+thread_local! {
+    static QUEUE: RefCell<Queue> = RefCell::new(Queue {
+        concurrency: DEFAULT_CONCURRENCY,
+        next_id: 0,
+        waiting: VecDeque::new(),
+        running: HashMap::new(),
+    });
+}
+
+struct Queue {
+    concurrency: usize,
+    next_id: u32,
+    waiting: VecDeque<Waiting>,
+    running: HashMap<u32, Running>,
+}
+
+struct Waiting {
+    id: u32,
+    key: String,
+    origin: String,
+    /// Resolves the request's start promise with its `AbortController`.
+    start: Function,
+    cancel: Function,
+}
+
+struct Running {
+    key: String,
+    origin: String,
+    controller: AbortController,
+    abort_reason: Option<Error>,
+}
+
+impl Running {
+    fn abort(&mut self, reason: Error) {
+        self.abort_reason = Some(reason);
+        self.controller.abort();
+    }
+}
+
+impl Queue {
+    fn next_startable(&mut self) -> Option<Waiting> {
+        if self.running.len() >= self.concurrency {
+            return None;
+        }
+        let running = &self.running;
+        let i = self.waiting.iter().position(|w| {
+            running.values().filter(|r| r.origin == w.origin).count() < CONCURRENCY_PER_ORIGIN
+        })?;
+        self.waiting.remove(i)
+    }
+}
+
+/// Starts as many waiting requests as the limits allow.
+fn pump() {
+    loop {
+        let started = QUEUE.with(|queue| {
+            let mut queue = queue.borrow_mut();
+            let waiting = queue.next_startable()?;
+            let controller = AbortController::new().unwrap();
+            let running = Running {
+                key: waiting.key,
+                origin: waiting.origin,
+                controller: controller.clone(),
+                abort_reason: None,
+            };
+            queue.running.insert(waiting.id, running);
+            Some((waiting.start, controller))
+        });
+        match started {
+            Some((start, controller)) => {
+                start.call1(&JsValue::null(), &controller).unwrap();
+            }
+            None => break,
+        }
+    }
+}
+
+/// Frees the slot of request `id`, telling why it was aborted if it was.
+fn finish(id: u32) -> Option<Error> {
+    let running = QUEUE.with(|queue| queue.borrow_mut().running.remove(&id));
+    pump();
+    running.and_then(|r| r.abort_reason)
+}
 
-    fetch(request)
+fn abort_after(id: u32, msec: i32) {
+    let closure = Closure::once(move || {
+        QUEUE.with(|queue| {
+            if let Some(running) = queue.borrow_mut().running.get_mut(&id) {
+                running.abort(Error::Timeout);
+            }
+        });
+    });
+    web_sys::window()
+        .unwrap()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(
+            closure.as_ref().unchecked_ref(),
+            msec,
+        )
+        .unwrap();
+    closure.forget();
 }
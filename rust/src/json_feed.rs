@@ -0,0 +1,57 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A JSON Feed 1.0 or 1.1 document, see https://jsonfeed.org/version/1.1
+#[derive(Deserialize, Debug)]
+pub struct JsonFeed {
+    pub version: String,
+    pub title: String,
+    #[serde(default)]
+    pub home_page_url: Option<String>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Item {
+    // Strings by the spec, but numbers show up in the wild.
+    pub id: Value,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub external_url: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub date_published: Option<String>,
+    #[serde(default)]
+    pub date_modified: Option<String>,
+}
+
+impl Item {
+    pub fn id(&self) -> Option<String> {
+        match self.id {
+            Value::String(ref id) => Some(id.clone()),
+            Value::Number(ref id) => Some(id.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        self.url
+            .as_ref()
+            .or_else(|| self.external_url.as_ref())
+            .map_or("", String::as_str)
+    }
+}
+
+pub fn is_json_feed(content_type: Option<&str>, body: &str) -> bool {
+    if let Some(content_type) = content_type {
+        if content_type.contains("json") {
+            return true;
+        }
+    }
+    body.trim_start().starts_with('{')
+}
@@ -6,6 +6,7 @@ extern crate chrono;
 extern crate console_error_panic_hook;
 extern crate futures;
 extern crate js_sys;
+extern crate quick_xml;
 extern crate rss;
 extern crate serde;
 extern crate serde_json;
@@ -16,17 +17,16 @@ extern crate wasm_bindgen;
 extern crate wasm_bindgen_futures;
 extern crate web_sys;
 
-use atom_syndication::Feed as AtomFeed;
+use chrono::Duration;
 use console_error_panic_hook::set_once as set_panic_hook;
 use futures::Future;
-use js_sys::{Array, Function, Promise, Uint8Array, encode_uri_component};
-use rss::Channel;
+use js_sys::{Array, Date, Function, Promise, Uint8Array, encode_uri_component};
 use serde_json::json;
 use squark::{App, Child, HandlerArg, Runtime, Task, View};
 use squark_macros::view;
 use squark_web::WebRuntime;
+use std::collections::hash_map::Entry;
 use std::iter::FromIterator;
-use std::str::FromStr;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
@@ -34,17 +34,30 @@ use web_sys::{
     console, window, Blob, BlobPropertyBag, Event, FileReader, HtmlAnchorElement, HtmlInputElement,
     HtmlLinkElement, Url, VisibilityState,
 };
+use wasm_bindgen_futures::future_to_promise;
 
+mod background;
+mod backup;
+mod date;
+mod discovery;
 mod fetch;
+mod json_feed;
+mod notification;
+mod opml;
+mod rdf;
+mod schedule;
 mod state;
+mod store;
 
-use state::{Feed, State};
+use backup::{ImportMode, PendingImport, Restored, Summary};
+use discovery::Candidate;
+use opml::Outline;
+use state::{Feed, FeedError, NotificationMode, State};
 
-const STATE_KEY: &str = "state";
-const AUTO_RELOAD_MINUTES: i32 = 5;
+/// Article titles shown when previewing a new feed.
+const PREVIEW_ARTICLES: usize = 5;
 
 const DEFAULT_TITLE: &str = "wino";
-const HIGHLIGHT_TITLE: &str = "(*)wino";
 
 #[wasm_bindgen]
 extern "C" {
@@ -54,27 +67,102 @@ extern "C" {
     #[wasm_bindgen(method, getter)]
     fn permissions(this: &Chrome) -> Permissions;
 
+    #[wasm_bindgen(method, getter)]
+    fn runtime(this: &Chrome) -> ChromeRuntime;
+    #[wasm_bindgen(method, getter, js_name = browserAction)]
+    fn browser_action(this: &Chrome) -> BrowserAction;
+    #[wasm_bindgen(method, getter)]
+    fn notifications(this: &Chrome) -> Notifications;
+    #[wasm_bindgen(method, getter)]
+    fn tabs(this: &Chrome) -> Tabs;
+
     type Permissions;
     #[wasm_bindgen(method)]
     fn request(this: &Permissions, arg: &JsValue, cb: &Function);
     #[wasm_bindgen(method)]
+    fn contains(this: &Permissions, arg: &JsValue, cb: &Function);
+    #[wasm_bindgen(method)]
     fn remove(this: &Permissions, arg: &JsValue);
+
+    type BrowserAction;
+    #[wasm_bindgen(method, js_name = setBadgeText)]
+    fn set_badge_text(this: &BrowserAction, details: &JsValue);
+    #[wasm_bindgen(method, js_name = setBadgeBackgroundColor)]
+    fn set_badge_background_color(this: &BrowserAction, details: &JsValue);
+
+    type Notifications;
+    #[wasm_bindgen(method)]
+    fn create(this: &Notifications, id: &str, options: &JsValue);
+    #[wasm_bindgen(method)]
+    fn clear(this: &Notifications, id: &str);
+    #[wasm_bindgen(method, getter, js_name = onClicked)]
+    fn on_clicked(this: &Notifications) -> ChromeEvent;
+    #[wasm_bindgen(method, getter, js_name = onClosed)]
+    fn on_closed(this: &Notifications) -> ChromeEvent;
+
+    type Tabs;
+    #[wasm_bindgen(method)]
+    fn create(this: &Tabs, properties: &JsValue);
+
+    type ChromeRuntime;
+    #[wasm_bindgen(method, js_name = sendMessage)]
+    fn send_message(this: &ChromeRuntime, message: &JsValue);
+    #[wasm_bindgen(method, getter, js_name = onMessage)]
+    fn on_message(this: &ChromeRuntime) -> ChromeEvent;
+
+    type ChromeEvent;
+    #[wasm_bindgen(method, js_name = addListener)]
+    fn add_listener(this: &ChromeEvent, cb: &Function);
 }
 
 #[derive(Clone, Debug)]
 enum Action {
     Empty,
-    Fetch(String),
+    Preview(String),
+    PreviewFetched(String, fetch::Response),
+    PreviewFailed(String, FeedError),
+    ConfirmPreview,
+    CancelPreview,
     UpdateNewFeedUrl(String),
     RemoveFeed(String),
     ToggleFeedVisible(String),
+    RenameFeed(String),
+    SetFeedFolder(String),
+    OpenArticle(String, String),
+    ToggleRead(String, String),
+    ToggleHideRead,
+    ToggleStar(String, String),
+    ToggleStarredView,
+    MarkFeedRead(String),
+    MarkVisibleRead,
+    UpdateOlderThanDays(u32),
+    MarkOlderThanRead,
     AddFeed,
-    Fetched(String, String),
+    Subscribe(String),
+    Discovered(String, Vec<Candidate>),
+    CancelDiscovery,
+    Fetched(String, fetch::Response),
+    FetchFailed(String, FeedError),
+    MoveFeed(String, String),
+    ToggleRemoveGoneFeeds,
+    CycleNotifications(String),
+    UpdateFetchConcurrency(usize),
     Reload,
-    AutoReload,
-    Export,
+    Sync(store::Change),
+    Seen,
+    FetchDue(String, f64),
+    SetRefreshInterval(String),
+    Export(bool),
+    ExportOpml,
     StartImport,
-    Import(State),
+    StartImportOpml,
+    Import(Restored),
+    CommitImport(ImportMode),
+    CancelImport,
+    ImportFailed(String),
+    DismissNotice,
+    ImportOpml(Vec<Outline>),
+    SubscribeOutlines(Vec<Outline>),
     ToggleSidebar,
 }
 
@@ -92,60 +180,260 @@ impl WinoApp {
             }
             Action::AddFeed => {
                 let new_feed_url = state.new_feed_url.clone();
-                let future = request_permission(&[new_feed_url.clone()]).map(|b| {
+                self._reducer(state, Action::Subscribe(new_feed_url))
+            }
+            Action::Subscribe(url) => {
+                state.discovered_feeds.clear();
+                let future = request_permission(&[url.clone()]).map(|b| {
                     if b {
-                        return Action::Fetch(new_feed_url);
+                        return Action::Preview(url);
                     }
                     Action::Empty
                 });
                 task.push(Box::new(future));
                 (state, task)
             }
-            Action::Fetch(url) => {
-                let future = fetch::get(&url)
-                    .map(move |body| Action::Fetched(url, body.as_string().unwrap()))
-                    .map_err(|_| ());
+            Action::Discovered(page_url, candidates) => {
+                state.is_loading_new_feed = false;
+                if candidates.is_empty() {
+                    state.notice = Some(format!("No feeds found at {}", page_url));
+                }
+                state.discovered_feeds = candidates;
+                (state, task)
+            }
+            Action::CancelDiscovery => {
+                state.discovered_feeds.clear();
+                (state, task)
+            }
+            Action::Preview(url) => {
+                let future = fetch::get(&url).then(move |result| {
+                    Ok(match result {
+                        Ok(resp) => Action::PreviewFetched(url, resp),
+                        Err(e) => Action::PreviewFailed(url, e.into()),
+                    })
+                });
                 task.push(Box::new(future));
                 state.new_feed_url = "".to_string();
+                state.is_loading_new_feed = true;
                 (state, task)
             }
-            Action::AutoReload => {
-                task.push(Box::new(timeout(
-                    Action::AutoReload,
-                    1000 * 60 * AUTO_RELOAD_MINUTES,
-                )));
-                task.push(Box::new(timeout(Action::Reload, 0)));
+            Action::PreviewFetched(url, resp) => {
+                state.is_loading_new_feed = false;
+                let content_type = resp.content_type();
+                // Feeds are often served as text/html, so parsing comes first.
+                let e = match Feed::parse(url.clone(), content_type, &resp.body) {
+                    Ok(feed) => {
+                        state.preview_feed = Some(Feed {
+                            etag: resp.header("ETag").map(String::from),
+                            last_modified: resp.header("Last-Modified").map(String::from),
+                            ..feed
+                        });
+                        return (state, task);
+                    }
+                    Err(e) => e,
+                };
+                // A website rather than a feed was added; offer the feeds it points to.
+                if discovery::is_html(content_type, &resp.body) {
+                    let candidates = discovery::discover(&resp.url, &resp.body);
+                    if !candidates.is_empty() {
+                        return self._reducer(state, Action::Discovered(url, candidates));
+                    }
+                    let future = discovery::probe(&url)
+                        .map(move |candidates| Action::Discovered(url, candidates));
+                    task.push(Box::new(future));
+                    state.is_loading_new_feed = true;
+                    return (state, task);
+                }
+                self._reducer(state, Action::PreviewFailed(url, e))
+            }
+            Action::PreviewFailed(url, e) => {
+                state.is_loading_new_feed = false;
+                state.notice = Some(format!("Could not load {}: {}", url, e));
                 (state, task)
             }
-            Action::Reload => {
-                {
-                    let feed_list = state.feed_map.values().cloned();
-                    for feed in feed_list {
-                        let future = fetch::get(&feed.url)
-                            .map(move |body| {
-                                Action::Fetched(feed.url.to_owned(), body.as_string().unwrap())
-                            })
-                            .map_err(|_| ());
-                        task.push(Box::new(future));
+            Action::ConfirmPreview => {
+                let mut fetched = match state.preview_feed.take() {
+                    Some(feed) => feed,
+                    None => return (state, task),
+                };
+                let now = Date::now();
+                fetched.status.last_success = Some(now);
+                match state.feed_map.entry(fetched.url.clone()) {
+                    Entry::Occupied(e) => {
+                        // The preview just loaded, so a feed given up on
+                        // as dead is polled again.
+                        let feed = e.into_mut();
+                        feed.merge(fetched);
+                        feed.status.record_success(now);
+                        feed.status.clear_redirect();
+                        feed.status.next_fetch = feed.next_fetch_after(now);
+                    }
+                    Entry::Vacant(e) => {
+                        fetched.status.next_fetch = fetched.next_fetch_after(now);
+                        e.insert(fetched);
                     }
                 }
                 (state, task)
             }
-            Action::Fetched(feed_url, resp) => {
-                if let Ok(atom) = AtomFeed::from_str(&resp) {
-                    let feed = Feed::from_atom(feed_url.clone(), &atom);
-                    state.feed_map.insert(feed_url.clone(), feed);
+            Action::CancelPreview => {
+                state.preview_feed = None;
+                (state, task)
+            }
+            Action::Sync(change) => (change.apply(state), task),
+            Action::Seen => {
+                state.unseen_count = 0;
+                (state, task)
+            }
+            Action::FetchDue(url, next_fetch) => {
+                // Timers from earlier schedules are left running; only the
+                // one set for the feed's current `next_fetch` goes through,
+                // waiting again if it fired early.
+                if let Some(feed) = state.feed_map.get(&url) {
+                    if feed.status.next_fetch != next_fetch {
+                        return (state, task);
+                    }
+                    if feed.is_due(Date::now()) {
+                        task.push(Box::new(fetch_feed(feed)));
+                    } else if !feed.status.dead {
+                        task.push(Box::new(background::fetch_when_due(feed)));
+                    }
+                }
+                (state, task)
+            }
+            Action::SetRefreshInterval(url) => {
+                let minutes = state
+                    .feed_map
+                    .get(&url)
+                    .and_then(|f| f.refresh_minutes)
+                    .map_or("".to_string(), |m| m.to_string());
+                let input = window()
+                    .unwrap()
+                    .prompt_with_message_and_default(
+                        "Refresh every how many minutes? Leave empty to follow the feed.",
+                        &minutes,
+                    )
+                    .unwrap();
+                let input = match input {
+                    Some(input) => input,
+                    None => return (state, task),
+                };
+                let refresh_minutes = match input.trim().parse::<u32>() {
+                    Ok(minutes) if minutes > 0 => Some(minutes),
+                    _ if input.trim().is_empty() => None,
+                    _ => return (state, task),
+                };
+                if let Some(feed) = state.feed_map.get_mut(&url) {
+                    feed.refresh_minutes = refresh_minutes;
+                    let last_fetch = feed.status.last_success.unwrap_or_else(Date::now);
+                    feed.status.next_fetch = feed.next_fetch_after(last_fetch);
+                }
 
+                (state, task)
+            }
+            // Polling belongs to the background script; see `background::BackgroundApp`.
+            Action::Reload => {
+                chrome
+                    .runtime()
+                    .send_message(&JsValue::from_str(background::RELOAD_MESSAGE));
+                (state, task)
+            }
+            Action::Fetched(feed_url, resp) => {
+                // The feed may have been removed while it was being fetched.
+                if !state.feed_map.contains_key(&feed_url) {
                     return (state, task);
                 }
+                if !resp.is_not_modified() {
+                    let fetched =
+                        match Feed::parse(feed_url.clone(), resp.content_type(), &resp.body) {
+                            Ok(feed) => feed,
+                            Err(e) => return self._reducer(state, Action::FetchFailed(feed_url, e)),
+                        };
+                    let feed = state.feed_map.get_mut(&feed_url).unwrap();
+                    feed.merge(fetched);
+                    feed.etag = resp.header("ETag").map(String::from);
+                    feed.last_modified = resp.header("Last-Modified").map(String::from);
+                }
 
-                let rss = Channel::from_str(&resp).unwrap();
-                let feed = Feed::from_rss(feed_url.clone(), &rss);
-                state.feed_map.insert(feed_url.clone(), feed);
+                let moved = match state.feed_map.get_mut(&feed_url) {
+                    Some(feed) => {
+                        let now = Date::now();
+                        feed.status.record_success(now);
+                        feed.status.next_fetch = feed.next_fetch_after(now);
+                        if resp.redirected && resp.url != feed_url {
+                            feed.status.record_redirect(&resp.url)
+                        } else {
+                            feed.status.clear_redirect();
+                            false
+                        }
+                    }
+                    None => false,
+                };
+                if moved {
+                    return self._reducer(state, Action::MoveFeed(feed_url, resp.url));
+                }
+
+                (state, task)
+            }
+            Action::MoveFeed(from, to) => {
+                state.move_feed(&from, to.clone());
+                // Moves happen after a fetch, with no user gesture to request
+                // permissions with; the old one goes only if the new one is
+                // already granted and no other feed shares the old origin.
+                let from_origin = origin(&from);
+                let is_origin_used = state.feed_map.keys().any(|url| origin(url) == from_origin);
+                let future = contains_permission(&[to]).map(move |b| {
+                    if b && !is_origin_used {
+                        remove_permission(&from);
+                    }
+                    Action::Empty
+                });
+                task.push(Box::new(future));
+
+                (state, task)
+            }
+            Action::FetchFailed(feed_url, e) => {
+                console::log_1(&format!("failed to fetch {}: {}", feed_url, e).into());
+                if e == FeedError::Gone && state.remove_gone_feeds {
+                    return self._reducer(state, Action::RemoveFeed(feed_url));
+                }
+                if let Some(feed) = state.feed_map.get_mut(&feed_url) {
+                    let interval = feed.refresh_interval();
+                    feed.status.record_failure(e, interval, Date::now());
+                }
+
+                (state, task)
+            }
+            Action::UpdateFetchConcurrency(concurrency) => {
+                state.fetch_concurrency = concurrency;
+                (state, task)
+            }
+            Action::CycleNotifications(url) => {
+                state
+                    .feed_map
+                    .entry(url)
+                    .and_modify(|f| f.notifications = f.notifications.next());
+                (state, task)
+            }
+            Action::ToggleRemoveGoneFeeds => {
+                state.remove_gone_feeds = !state.remove_gone_feeds;
+                if !state.remove_gone_feeds {
+                    return (state, task);
+                }
+                let dead_urls: Vec<String> = state
+                    .feed_map
+                    .values()
+                    .filter(|f| f.status.dead)
+                    .map(|f| f.url.clone())
+                    .collect();
+                for url in dead_urls {
+                    state.feed_map.remove(&url);
+                    remove_permission(&url);
+                }
 
                 (state, task)
             }
             Action::RemoveFeed(url) => {
+                fetch::cancel(&url);
                 state.feed_map.remove(&url);
                 remove_permission(&url);
 
@@ -159,74 +447,213 @@ impl WinoApp {
 
                 (state, task)
             }
-            Action::Export => {
-                let data = bincode::serialize(&state).unwrap();
-                console::log_1(&format!("{:?}", data).into());
-                let b = Uint8Array::new(&unsafe { Uint8Array::view(&data) }.into());
-                let mut options = BlobPropertyBag::new();
-                options.type_("application/octet-stream");
-                let array = Array::new();
-                array.push(&b.buffer());
-                let blob = Blob::new_with_u8_array_sequence_and_options(&array, &options).unwrap();
-                let window = window().unwrap();
-                let document = window.document().unwrap();
-                let body = document.body().unwrap();
-                let a = document.create_element("a").unwrap();
-                let url = Url::create_object_url_with_blob(&blob).unwrap();;
-                (a.unchecked_ref() as &HtmlLinkElement).set_href(&url);
-                (a.unchecked_ref() as &HtmlAnchorElement).set_download("wino_export");
-                body.append_child(&a).unwrap();
-                (a.unchecked_ref() as &HtmlLinkElement).click();
-                Url::revoke_object_url(&url).unwrap();
-                body.remove_child(&a).unwrap();
+            Action::RenameFeed(url) => {
+                let title = state.feed_map.get(&url).map_or("", Feed::display_title);
+                let input = window()
+                    .unwrap()
+                    .prompt_with_message_and_default("Feed title", title)
+                    .unwrap();
+                if let Some(input) = input {
+                    state.feed_map.entry(url).and_modify(|f| {
+                        f.custom_title = if input.is_empty() { None } else { Some(input) };
+                    });
+                }
 
                 (state, task)
             }
-            Action::StartImport => {
-                let window = window().unwrap();
-                let document = window.document().unwrap();
-                let import: HtmlInputElement = document
-                    .get_element_by_id("import")
+            Action::SetFeedFolder(url) => {
+                let folder = state
+                    .feed_map
+                    .get(&url)
+                    .and_then(|f| f.folder.clone())
+                    .unwrap_or_default();
+                let input = window()
                     .unwrap()
-                    .unchecked_into();
-                let file = import.files().unwrap().get(0).unwrap();
-                import.set_files(None);
-                let file_reader = FileReader::new().unwrap();
-                let file_reader_1 = file_reader.clone();
-
-                let p = Promise::new(&mut move |resolve, _| {
-                    let file_reader_2 = file_reader_1.clone();
-                    let closure = Closure::wrap(Box::new(move |_: Event| {
-                        let array = Uint8Array::new(&file_reader_2.clone().result().unwrap());
-                        resolve.call1(&JsValue::null(), &array.into()).unwrap();
-                    }) as Box<FnMut(_)>);
-                    file_reader_1
-                        .clone()
-                        .set_onload(Some(closure.as_ref().unchecked_ref()));
-                    closure.forget();
+                    .prompt_with_message_and_default("Folder", &folder)
+                    .unwrap();
+                if let Some(input) = input {
+                    state.feed_map.entry(url).and_modify(|f| {
+                        f.folder = if input.is_empty() { None } else { Some(input) };
+                    });
+                }
+
+                (state, task)
+            }
+            Action::OpenArticle(feed_url, id) => {
+                if let Some(article) = state.get_article_mut(&feed_url, &id) {
+                    article.read = true;
+                }
+
+                (state, task)
+            }
+            Action::ToggleRead(feed_url, id) => {
+                if let Some(article) = state.get_article_mut(&feed_url, &id) {
+                    article.read = !article.read;
+                }
+
+                (state, task)
+            }
+            Action::ToggleHideRead => {
+                state.hide_read = !state.hide_read;
+                (state, task)
+            }
+            Action::ToggleStar(feed_url, id) => {
+                if let Some(article) = state.get_article_mut(&feed_url, &id) {
+                    article.starred = !article.starred;
+                }
+
+                (state, task)
+            }
+            Action::ToggleStarredView => {
+                state.is_showing_starred = !state.is_showing_starred;
+                (state, task)
+            }
+            Action::MarkFeedRead(url) => {
+                state.feed_map.entry(url).and_modify(Feed::mark_all_read);
+                (state, task)
+            }
+            Action::MarkVisibleRead => {
+                state.mark_visible_read();
+                (state, task)
+            }
+            Action::UpdateOlderThanDays(days) => {
+                state.older_than_days = days;
+                (state, task)
+            }
+            Action::MarkOlderThanRead => {
+                let cutoff = date::now() - Duration::days(i64::from(state.older_than_days));
+                state.mark_older_than_read(cutoff);
+                (state, task)
+            }
+            Action::Export(compressed) => {
+                let json = backup::export(&state);
+                if !compressed {
+                    download(json.as_bytes(), "application/json", &backup::file_name(false));
+                    return (state, task);
+                }
+
+                let future = backup::compress(json.into_bytes()).map(|data| {
+                    download(&data, "application/gzip", &backup::file_name(true));
+                    Action::Empty
                 });
-                file_reader.read_as_array_buffer(&file).unwrap();
-                let future = JsFuture::from(p)
-                    .map(|array| {
-                        let array: Uint8Array = array.unchecked_into();
-                        let mut buf = vec![0; array.length() as usize];
-                        array.copy_to(&mut buf);
-                        let state = bincode::deserialize(&buf).unwrap();
-                        Action::Import(state)
+                task.push(Box::new(future));
+
+                (state, task)
+            }
+            Action::ExportOpml => {
+                let opml = opml::export(state.feed_map.values());
+                download(opml.as_bytes(), "text/x-opml", "wino.opml");
+
+                (state, task)
+            }
+            Action::StartImport => {
+                let future = read_file("import").and_then(|buf| {
+                    backup::decompress(buf).then(|result| {
+                        Ok(match result.and_then(|buf| backup::restore(&buf)) {
+                            Ok(restored) => Action::Import(restored),
+                            Err(e) => Action::ImportFailed(e.to_string()),
+                        })
                     })
-                    .map_err(|e| panic!("delay errored; err={:?}", e));
+                });
                 task.push(Box::new(future));
 
                 (state, task)
             }
-            Action::Import(s) => {
-                let urls: Vec<String> = s.feed_map.values().map(|f| f.url.clone()).collect();
+            Action::StartImportOpml => {
+                let future = read_file("import_opml").map(|buf| {
+                    match opml::parse(&String::from_utf8_lossy(&buf)) {
+                        Ok(outlines) => Action::ImportOpml(outlines),
+                        Err(e) => Action::ImportFailed(format!("invalid OPML: {}", e)),
+                    }
+                });
+                task.push(Box::new(future));
+
+                (state, task)
+            }
+            Action::ImportOpml(outlines) => {
+                let (outlines, invalid): (Vec<Outline>, Vec<Outline>) = outlines
+                    .into_iter()
+                    .filter(|outline| !state.feed_map.contains_key(&outline.xml_url))
+                    .partition(|outline| {
+                        outline.xml_url.starts_with("http://")
+                            || outline.xml_url.starts_with("https://")
+                    });
+                if !invalid.is_empty() {
+                    let skipped: Vec<String> = invalid
+                        .iter()
+                        .map(|o| format!("\"{}\" (invalid URL {})", o.title, o.xml_url))
+                        .collect();
+                    state.notice = Some(format!("Skipped {}.", skipped.join(", ")));
+                }
+                let urls: Vec<String> = outlines.iter().map(|o| o.xml_url.clone()).collect();
+                let future = request_permission(&urls).map(|b| {
+                    if b {
+                        return Action::SubscribeOutlines(outlines);
+                    }
+                    Action::Empty
+                });
+                task.push(Box::new(future));
+
+                (state, task)
+            }
+            // The background script fetches the new feeds once synced.
+            Action::SubscribeOutlines(outlines) => {
+                for outline in outlines {
+                    if state.feed_map.contains_key(&outline.xml_url) {
+                        continue;
+                    }
+                    let feed = Feed {
+                        title: outline.title,
+                        url: outline.xml_url.clone(),
+                        site_url: outline.html_url,
+                        folder: outline.folder,
+                        ..Default::default()
+                    };
+                    state.feed_map.insert(outline.xml_url, feed);
+                }
+
+                (state, task)
+            }
+            Action::Import(restored) => {
+                let summary = Summary::new(&state.feed_map, &restored.feed_map);
+                state.pending_import = Some(PendingImport { restored, summary });
+                (state, task)
+            }
+            Action::CommitImport(mode) => {
+                let pending = match state.pending_import.take() {
+                    Some(pending) => pending,
+                    None => return (state, task),
+                };
+                let restored = pending.restored;
+                let urls: Vec<String> = restored
+                    .feed_map
+                    .keys()
+                    .filter(|url| mode == ImportMode::Replace || !state.feed_map.contains_key(*url))
+                    .cloned()
+                    .collect();
                 let future = request_permission(&urls).map(|b| {
                     console::log_1(&b.into());
                     Action::Empty
                 });
                 task.push(Box::new(future));
-                (s, task)
+                match mode {
+                    ImportMode::Merge => backup::merge(&mut state.feed_map, restored.feed_map),
+                    ImportMode::Replace => state.feed_map = restored.feed_map,
+                }
+                state.notice = Some(restored.report.to_string());
+                (state, task)
+            }
+            Action::CancelImport => {
+                state.pending_import = None;
+                (state, task)
+            }
+            Action::ImportFailed(e) => {
+                state.notice = Some(format!("Import failed: {}", e));
+                (state, task)
+            }
+            Action::DismissNotice => {
+                state.notice = None;
+                (state, task)
             }
             Action::ToggleSidebar => {
                 state.is_opening_sidebar = !state.is_opening_sidebar;
@@ -242,21 +669,28 @@ impl App for WinoApp {
 
     fn reducer(&self, state: State, action: Action) -> (State, Task<Action>) {
         let old_state = state.clone();
+        // Synced state is already stored; writing it back would bounce it
+        // between windows.
+        let is_sync = match action {
+            Action::Sync(_) => true,
+            _ => false,
+        };
 
-        let (state, task) = self._reducer(state, action);
+        let (mut state, task) = self._reducer(state, action);
 
         if state != old_state {
-            let window = window().unwrap();
-            let document = window.document().unwrap();
+            let document = window().unwrap().document().unwrap();
 
             if document.visibility_state() == VisibilityState::Hidden {
-                document.set_title(HIGHLIGHT_TITLE);
+                state.unseen_count += state.new_article_count(&old_state);
+            }
+            if state.unseen_count != old_state.unseen_count {
+                document.set_title(&title(state.unseen_count));
             }
 
-            let storage = window.local_storage().unwrap().unwrap();
-            storage
-                .set_item(STATE_KEY, &serde_json::to_string(&state).unwrap())
-                .unwrap();
+            if !is_sync {
+                store::save(&old_state, &state);
+            }
         }
 
         (state, task)
@@ -268,6 +702,26 @@ impl App for WinoApp {
         } else {
             ("is-hidden", "")
         };
+        let hide_read_label = if state.hide_read {
+            "show read"
+        } else {
+            "hide read"
+        };
+        let remove_gone_label = if state.remove_gone_feeds {
+            "keep gone feeds"
+        } else {
+            "remove gone feeds"
+        };
+        let new_feed_control_class = if state.is_loading_new_feed {
+            "control is-loading"
+        } else {
+            "control"
+        };
+        let starred_class = if state.is_showing_starred {
+            "list-item is-active"
+        } else {
+            "list-item"
+        };
         view! {
             <div>
                 <div class={ menu_button_class } style="position: fixed; top: 16px; left: 16px; opacity: 0.5; z-index: 30">
@@ -300,7 +754,56 @@ impl App for WinoApp {
                             <a class="button is-fullwidth" onclick={ |_| Some(Action::Reload) }>reload</a>
                         </div>
                         <div>
-                            <a class="button is-fullwidth" onclick={ |_| Some(Action::Export) }>export</a>
+                            <a class="button is-fullwidth" onclick={ |_| Some(Action::ToggleHideRead) }>{ hide_read_label }</a>
+                        </div>
+                        <div>
+                            <a class="button is-fullwidth" onclick={ |_| Some(Action::MarkVisibleRead) }>mark visible read</a>
+                        </div>
+                        <div class="field has-addons">
+                            <div class="control">
+                                <a class="button" onclick={ |_| Some(Action::MarkOlderThanRead) }>mark read older than</a>
+                            </div>
+                            <div class="control">
+                                <input
+                                    class="input"
+                                    type="number"
+                                    min="1"
+                                    value={ state.older_than_days.to_string() }
+                                    oninput={ |v| match v {
+                                        HandlerArg::String(ref v) => v.parse().ok().map(Action::UpdateOlderThanDays),
+                                        _ => None,
+                                    } }
+                                />
+                            </div>
+                            <div class="control">
+                                <a class="button is-static">days</a>
+                            </div>
+                        </div>
+                        <div>
+                            <a class="button is-fullwidth" onclick={ |_| Some(Action::ToggleRemoveGoneFeeds) }>{ remove_gone_label }</a>
+                        </div>
+                        <div class="field has-addons">
+                            <div class="control">
+                                <a class="button is-static">parallel fetches</a>
+                            </div>
+                            <div class="control">
+                                <input
+                                    class="input"
+                                    type="number"
+                                    min="1"
+                                    value={ state.fetch_concurrency.to_string() }
+                                    oninput={ |v| match v {
+                                        HandlerArg::String(ref v) => v.parse().ok().filter(|n| *n > 0).map(Action::UpdateFetchConcurrency),
+                                        _ => None,
+                                    } }
+                                />
+                            </div>
+                        </div>
+                        <div>
+                            <a class="button is-fullwidth" onclick={ |_| Some(Action::Export(false)) }>export</a>
+                        </div>
+                        <div>
+                            <a class="button is-fullwidth" onclick={ |_| Some(Action::Export(true)) }>export (gzip)</a>
                         </div>
                         <div>
                             <label>
@@ -308,9 +811,47 @@ impl App for WinoApp {
                                 <input class="is-invisible" id="import" type="file" onchange={ |_| Some(Action::StartImport) }></input>
                             </label>
                         </div>
+                        <div>
+                            <a class="button is-fullwidth" onclick={ |_| Some(Action::ExportOpml) }>export OPML</a>
+                        </div>
+                        <div>
+                            <label>
+                                <a class="button is-fullwidth">import OPML</a>
+                                <input class="is-invisible" id="import_opml" type="file" accept=".opml,.xml" onchange={ |_| Some(Action::StartImportOpml) }></input>
+                            </label>
+                        </div>
+                        {
+                            state.notice.clone().map(|notice| view! {
+                                <div class="notification is-info">
+                                    <button class="delete" onclick={ |_| Some(Action::DismissNotice) }></button>
+                                    { notice }
+                                </div>
+                            })
+                        }
+                        {
+                            state.pending_import.clone().map(|pending| {
+                                let summary = pending.summary;
+                                view! {
+                                    <div class="notification is-warning">
+                                        <p>{ pending.restored.report.to_string() }</p>
+                                        <p>{ format!("New ({}): {}", summary.added.len(), summary.added.join(", ")) }</p>
+                                        <p>{ format!("Updated ({}): {}", summary.updated.len(), summary.updated.join(", ")) }</p>
+                                        <p>{ format!("Conflicting, local settings kept on merge ({}): {}", summary.conflicting.len(), summary.conflicting.join(", ")) }</p>
+                                        <div class="buttons">
+                                            <a class="button is-primary" onclick={ |_| Some(Action::CommitImport(ImportMode::Merge)) }>merge</a>
+                                            <a class="button is-danger" onclick={ |_| Some(Action::CommitImport(ImportMode::Replace)) }>replace</a>
+                                            <a class="button" onclick={ |_| Some(Action::CancelImport) }>cancel</a>
+                                        </div>
+                                    </div>
+                                }
+                            })
+                        }
+                        <section>
+                            <p>{ format!("{} unread", state.unread_count()) }</p>
+                        </section>
                         <section>
                             <h2>Add Feed</h2>
-                            <div>
+                            <div class={ new_feed_control_class }>
                                 <input
                                     class="input"
                                     value={ state.new_feed_url.clone() }
@@ -326,17 +867,113 @@ impl App for WinoApp {
                                     } }
                                 />
                             </div>
+                            {
+                                state.preview_feed.clone().map(|feed| {
+                                    let articles = feed.newest_articles();
+                                    let date_range = match (articles.first(), articles.last()) {
+                                        (Some(newest), Some(oldest)) => format!(
+                                            "{} articles, {} to {}",
+                                            articles.len(),
+                                            oldest.date.format("%Y-%m-%d"),
+                                            newest.date.format("%Y-%m-%d")
+                                        ),
+                                        _ => "no articles".to_string(),
+                                    };
+                                    view! {
+                                        <div class="notification">
+                                            <p class="has-text-weight-bold">{ feed.display_title().to_string() }</p>
+                                            <p>{ date_range }</p>
+                                            <ul>
+                                                {
+                                                    Child::from_iter(
+                                                        articles.iter().take(PREVIEW_ARTICLES).map(|article| view! {
+                                                            <li>{ article.title.clone() }</li>
+                                                        })
+                                                    )
+                                                }
+                                            </ul>
+                                            <div class="buttons">
+                                                <a class="button is-primary" onclick={ |_| Some(Action::ConfirmPreview) }>subscribe</a>
+                                                <a class="button" onclick={ |_| Some(Action::CancelPreview) }>cancel</a>
+                                            </div>
+                                        </div>
+                                    }
+                                })
+                            }
+                            {
+                                if state.discovered_feeds.is_empty() {
+                                    None
+                                } else {
+                                    Some(view! {
+                                        <div class="notification">
+                                            <button class="delete" onclick={ |_| Some(Action::CancelDiscovery) }></button>
+                                            <p>Feeds found on this site:</p>
+                                            <div class="list is-hoverable">
+                                                {
+                                                    Child::from_iter(
+                                                        state.discovered_feeds.clone().into_iter().map(|candidate| {
+                                                            let url = candidate.url.clone();
+                                                            view! {
+                                                                <a class="list-item" title={ candidate.url } onclick={ move |_| Some(Action::Subscribe(url.to_owned())) }>
+                                                                    { candidate.title }
+                                                                </a>
+                                                            }
+                                                        })
+                                                    )
+                                                }
+                                            </div>
+                                        </div>
+                                    })
+                                }
+                            }
                             <div>
                                 <div class="list is-hoverable">
+                                <a class={ starred_class } onclick={ |_| Some(Action::ToggleStarredView) }>
+                                    <div class="level">
+                                        <div class="level-left">
+                                            <span class="icon">
+                                                <i class="fas fa-star"></i>
+                                            </span>
+                                            Starred
+                                        </div>
+                                        <div class="level-right">
+                                            <span class="tag is-rounded">{ state.starred_count().to_string() }</span>
+                                        </div>
+                                    </div>
+                                </a>
                                 {
+                                    let mut feed_vec: Vec<_> = state.feed_map.clone().into_iter().collect();
+                                    feed_vec.sort_by_key(|(_, feed)| {
+                                        (feed.folder.clone(), feed.display_title().to_lowercase())
+                                    });
+                                    let mut current_folder = None;
                                     Child::from_iter(
-                                        state.feed_map.clone().into_iter().enumerate().map(|(i, (key, feed))| {
+                                        feed_vec.into_iter().flat_map(|(key, feed)| {
+                                            let mut views = vec![];
+                                            if feed.folder != current_folder {
+                                                current_folder = feed.folder.clone();
+                                                if let Some(ref folder) = current_folder {
+                                                    views.push(view! {
+                                                        <p class="list-item has-text-weight-bold">{ folder.clone() }</p>
+                                                    });
+                                                }
+                                            }
+
                                             let key_1 = key.clone();
-                                            view! {
-                                                <a
-                                                    onclick={ move |_| Some(Action::ToggleFeedVisible(key.to_owned())) }
-                                                    class="list-item"
-                                                >
+                                            let key_2 = key.clone();
+                                            let key_3 = key.clone();
+                                            let key_4 = key.clone();
+                                            let key_5 = key.clone();
+                                            let key_6 = key.clone();
+                                            let notifications_title = format!("notifications: {}", feed.notifications);
+                                            let notifications_icon = if feed.notifications == NotificationMode::Off {
+                                                "fas fa-bell-slash"
+                                            } else {
+                                                "fas fa-bell"
+                                            };
+                                            let refresh_title = format!("refresh every {} minutes", feed.refresh_interval());
+                                            views.push(view! {
+                                                <a class="list-item">
                                                     <div class="level">
                                                         <div class="level-left">
                                                             <label class="checkbox">
@@ -344,17 +981,51 @@ impl App for WinoApp {
                                                                     class="checkbox"
                                                                     type="checkbox"
                                                                     checked={feed.visible}
+                                                                    onclick={ move |_| Some(Action::ToggleFeedVisible(key.to_owned())) }
                                                                 />
 
-                                                                { feed.title.clone() }
+                                                                { feed.display_title().to_string() }
                                                             </label>
+                                                            <span class="tag is-rounded">{ feed.unread_count().to_string() }</span>
+                                                            {
+                                                                feed.status.last_error.as_ref().map(|e| view! {
+                                                                    <span class="tag is-danger">{ e.to_string() }</span>
+                                                                })
+                                                            }
+                                                            {
+                                                                if feed.status.is_retrying() {
+                                                                    let next_retry = Date::new(&feed.status.next_fetch.into());
+                                                                    let label = format!("next retry at {}", String::from(next_retry.to_locale_time_string("default")));
+                                                                    Some(view! {
+                                                                        <span class="tag">{ label }</span>
+                                                                    })
+                                                                } else {
+                                                                    None
+                                                                }
+                                                            }
                                                         </div>
                                                         <div class="level-right">
+                                                            <a class="icon" title="mark all read" onclick={ move |_| Some(Action::MarkFeedRead(key_3.to_owned())) } >
+                                                                <i class="fas fa-check-double"></i>
+                                                            </a>
+                                                            <a class="icon" onclick={ move |_| Some(Action::RenameFeed(key_2.to_owned())) } >
+                                                                <i class="fas fa-pen"></i>
+                                                            </a>
+                                                            <a class="icon" title="move to folder" onclick={ move |_| Some(Action::SetFeedFolder(key_4.to_owned())) } >
+                                                                <i class="fas fa-folder"></i>
+                                                            </a>
+                                                            <a class="icon" title={ notifications_title } onclick={ move |_| Some(Action::CycleNotifications(key_6.to_owned())) } >
+                                                                <i class={ notifications_icon }></i>
+                                                            </a>
+                                                            <a class="icon" title={ refresh_title } onclick={ move |_| Some(Action::SetRefreshInterval(key_5.to_owned())) } >
+                                                                <i class="fas fa-clock"></i>
+                                                            </a>
                                                             <a class="delete" onclick={ move |_| Some(Action::RemoveFeed(key_1.to_owned())) } ></a>
                                                         </div>
                                                     </div>
                                                 </a>
-                                            }
+                                            });
+                                            views
                                         })
                                     )
                                 }
@@ -365,18 +1036,9 @@ impl App for WinoApp {
                     <div class="column is-small">
                         <div class="">
                         {
-                            let iter = state.feed_map
-                                .values()
-                                .filter(|feed| feed.visible)
-                                .flat_map(|feed| {
-                                    feed.article_map
-                                        .values()
-                                        .map(move |article| (feed.title.clone(), article))
-                                });
-                            let mut article_vec = Vec::from_iter(iter);
-                            article_vec.sort_by(|(_, a), (_, b)| b.date.cmp(&a.date));
+                            let article_vec = state.visible_articles();
                             Child::from_iter(
-                                article_vec.iter().map(|(feed_title, article)| {
+                                article_vec.into_iter().map(|(feed_url, feed, id, article)| {
                                     let encoded_url: String = encode_uri_component(&article.url).into();
                                     let encoded_title: String = encode_uri_component(&article.title).into();
                                     let tweet_url = format!(
@@ -390,33 +1052,69 @@ impl App for WinoApp {
                                         encoded_url
                                     );
 
+                                    let (title_class, read_label) = if article.read {
+                                        ("subtitle has-text-grey", "mark unread")
+                                    } else {
+                                        ("subtitle", "mark read")
+                                    };
+                                    let (feed_url_1, id_1) = (feed_url.to_string(), id.to_string());
+                                    let (feed_url_2, id_2) = (feed_url.to_string(), id.to_string());
+                                    let (feed_url_3, id_3) = (feed_url.to_string(), id.to_string());
+                                    let star_class = if article.starred {
+                                        "fas fa-star"
+                                    } else {
+                                        "far fa-star"
+                                    };
+
                                     view! {
-                                        <a target="_blank" href={ article.url.clone() }>
-                                            <div class="card">
-                                                <div class="card-content">
-                                                    <p class="subtitle">{ article.title.clone() }</p>
-                                                    <div class="level">
-                                                        <div class="level-left">
-                                                            <p>{ feed_title.clone() }</p>
-                                                        </div>
-                                                        <div class="level-right">
-                                                            <div class="columns is-1 is-variable">
-                                                                <div class="column">
-                                                                    <a href={ tweet_url } class="button" target="_new"> 
-                                                                        twitter
-                                                                    </a>
-                                                                </div>
-                                                                <div class="column">
-                                                                    <a href={ vein_url } class="button" target="_new"> 
-                                                                        vein
-                                                                    </a>
-                                                                </div>
+                                        <div class="card">
+                                            <div class="card-content">
+                                                <a
+                                                    target="_blank"
+                                                    href={ article.url.clone() }
+                                                    onclick={ move |_| Some(Action::OpenArticle(feed_url_1.to_owned(), id_1.to_owned())) }
+                                                >
+                                                    <p class={ title_class }>{ article.title.clone() }</p>
+                                                </a>
+                                                <div class="level">
+                                                    <div class="level-left">
+                                                        <p>{ feed.display_title().to_string() }</p>
+                                                    </div>
+                                                    <div class="level-right">
+                                                        <div class="columns is-1 is-variable">
+                                                            <div class="column">
+                                                                <a
+                                                                    class="button"
+                                                                    onclick={ move |_| Some(Action::ToggleStar(feed_url_3.to_owned(), id_3.to_owned())) }
+                                                                >
+                                                                    <span class="icon">
+                                                                        <i class={ star_class }></i>
+                                                                    </span>
+                                                                </a>
+                                                            </div>
+                                                            <div class="column">
+                                                                <a
+                                                                    class="button"
+                                                                    onclick={ move |_| Some(Action::ToggleRead(feed_url_2.to_owned(), id_2.to_owned())) }
+                                                                >
+                                                                    { read_label }
+                                                                </a>
+                                                            </div>
+                                                            <div class="column">
+                                                                <a href={ tweet_url } class="button" target="_new">
+                                                                    twitter
+                                                                </a>
+                                                            </div>
+                                                            <div class="column">
+                                                                <a href={ vein_url } class="button" target="_new">
+                                                                    vein
+                                                                </a>
                                                             </div>
                                                         </div>
                                                     </div>
                                                 </div>
                                             </div>
-                                        </a>
+                                        </div>
                                     }
                                 })
                             )
@@ -435,13 +1133,11 @@ impl Default for WinoApp {
     }
 }
 
-fn on_visibility_change() {
-    let window = window().unwrap();
-    let document = window.document().unwrap();
-
-    if document.visibility_state() == VisibilityState::Visible {
-        document.set_title(DEFAULT_TITLE);
+fn title(unseen_count: usize) -> String {
+    if unseen_count == 0 {
+        return DEFAULT_TITLE.to_string();
     }
+    format!("({}) {}", unseen_count, DEFAULT_TITLE)
 }
 
 fn request_permission(urls: &[String]) -> impl Future<Item = bool, Error = ()> {
@@ -461,6 +1157,96 @@ fn request_permission(urls: &[String]) -> impl Future<Item = bool, Error = ()> {
         .map_err(|e| panic!("delay errored; err={:?}", e))
 }
 
+fn contains_permission(urls: &[String]) -> impl Future<Item = bool, Error = ()> {
+    let arg = json!({ "origins": urls });
+    let p = Promise::new(&mut move |resolve, _| {
+        let closure = Closure::wrap(Box::new(move |b: bool| {
+            resolve.call1(&JsValue::null(), &b.into()).unwrap();
+        }) as Box<FnMut(_)>);
+        chrome.permissions().contains(
+            &JsValue::from_serde(&arg).unwrap(),
+            closure.as_ref().unchecked_ref(),
+        );
+        closure.forget();
+    });
+    JsFuture::from(p)
+        .map(|b| b.as_bool().unwrap())
+        .map_err(|e| panic!("permissions.contains errored; err={:?}", e))
+}
+
+fn origin(url: &str) -> Option<String> {
+    Url::new(url).ok().map(|url| url.origin())
+}
+
+fn fetch_feed(feed: &Feed) -> impl Future<Item = Action, Error = ()> {
+    let url = feed.url.clone();
+    let validators = fetch::Validators {
+        etag: feed.etag.clone(),
+        last_modified: feed.last_modified.clone(),
+    };
+    fetch::queued_get(&url, &url, validators).then(move |result| {
+        Ok(match result {
+            Ok(resp) => Action::Fetched(url, resp),
+            Err(fetch::Error::Cancelled) => Action::Empty,
+            Err(e) => Action::FetchFailed(url, e.into()),
+        })
+    })
+}
+
+fn download(data: &[u8], content_type: &str, filename: &str) {
+    let b = Uint8Array::new(&unsafe { Uint8Array::view(data) }.into());
+    let mut options = BlobPropertyBag::new();
+    options.type_(content_type);
+    let array = Array::new();
+    array.push(&b.buffer());
+    let blob = Blob::new_with_u8_array_sequence_and_options(&array, &options).unwrap();
+    let window = window().unwrap();
+    let document = window.document().unwrap();
+    let body = document.body().unwrap();
+    let a = document.create_element("a").unwrap();
+    let url = Url::create_object_url_with_blob(&blob).unwrap();
+    (a.unchecked_ref() as &HtmlLinkElement).set_href(&url);
+    (a.unchecked_ref() as &HtmlAnchorElement).set_download(filename);
+    body.append_child(&a).unwrap();
+    (a.unchecked_ref() as &HtmlLinkElement).click();
+    Url::revoke_object_url(&url).unwrap();
+    body.remove_child(&a).unwrap();
+}
+
+fn read_file(input_id: &str) -> impl Future<Item = Vec<u8>, Error = ()> {
+    let window = window().unwrap();
+    let document = window.document().unwrap();
+    let input: HtmlInputElement = document
+        .get_element_by_id(input_id)
+        .unwrap()
+        .unchecked_into();
+    let file = input.files().unwrap().get(0).unwrap();
+    input.set_files(None);
+    let file_reader = FileReader::new().unwrap();
+    let file_reader_1 = file_reader.clone();
+
+    let p = Promise::new(&mut move |resolve, _| {
+        let file_reader_2 = file_reader_1.clone();
+        let closure = Closure::wrap(Box::new(move |_: Event| {
+            let array = Uint8Array::new(&file_reader_2.clone().result().unwrap());
+            resolve.call1(&JsValue::null(), &array.into()).unwrap();
+        }) as Box<FnMut(_)>);
+        file_reader_1
+            .clone()
+            .set_onload(Some(closure.as_ref().unchecked_ref()));
+        closure.forget();
+    });
+    file_reader.read_as_array_buffer(&file).unwrap();
+    JsFuture::from(p)
+        .map(|array| {
+            let array: Uint8Array = array.unchecked_into();
+            let mut buf = vec![0; array.length() as usize];
+            array.copy_to(&mut buf);
+            buf
+        })
+        .map_err(|e| panic!("delay errored; err={:?}", e))
+}
+
 fn remove_permission(url: &str) {
     let arg = json!({ "origins": [url] });
     chrome
@@ -491,30 +1277,35 @@ fn timeout<T>(v: T, msec: i32) -> impl Future<Item = T, Error = ()> {
 pub fn run() {
     set_panic_hook();
 
-    let window = window().unwrap();
-    let document = window.document().unwrap();
+    let future = store::load().map(|state| {
+        let document = window().unwrap().document().unwrap();
+
+        let runtime = WebRuntime::<WinoApp>::new(
+            document.query_selector("#container").unwrap().unwrap(),
+            state,
+        );
 
-    let storage = window.local_storage().unwrap().unwrap();
+        let runtime_1 = runtime.clone();
+        store::listen(move |change| runtime_1.on_action(Action::Sync(change)));
 
-    let state = storage
-        .get_item(STATE_KEY)
-        .unwrap()
-        .map(|s| serde_json::from_str(&s).unwrap())
-        .unwrap_or_default();
-
-    let mut task = Task::empty();
-    task.push(Box::new(timeout(
-        Action::AutoReload,
-        0
-    )));
-
-    let closure = Closure::wrap(Box::new(on_visibility_change) as Box<Fn()>);
-    document.set_onvisibilitychange(Some(closure.as_ref().unchecked_ref()));
-    closure.forget();
-
-    WebRuntime::<WinoApp>::new(
-        document.query_selector("#container").unwrap().unwrap(),
-        state,
-    )
-    .run_with_task(task);
+        let runtime_2 = runtime.clone();
+        let document_1 = document.clone();
+        let closure = Closure::wrap(Box::new(move || {
+            if document_1.visibility_state() == VisibilityState::Visible {
+                runtime_2.on_action(Action::Seen);
+            }
+        }) as Box<Fn()>);
+        document.set_onvisibilitychange(Some(closure.as_ref().unchecked_ref()));
+        closure.forget();
+
+        runtime.run();
+        JsValue::null()
+    });
+    future_to_promise(future.map_err(|_| JsValue::null()));
+}
+
+#[wasm_bindgen]
+pub fn run_background() {
+    set_panic_hook();
+    background::run();
 }
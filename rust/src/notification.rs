@@ -0,0 +1,136 @@
+use serde_json::json;
+use state::{Article, NotificationMode, State};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use {chrome, Action};
+
+/// A fetch bringing more new articles than this gets one summary instead of
+/// a notification per article.
+const MAX_ITEM_NOTIFICATIONS: usize = 3;
+/// Article titles listed in a digest.
+const DIGEST_TITLES: usize = 5;
+const ICON_URL: &str = "/icon.png";
+const READER_URL: &str = "/dist/index.html";
+
+/// What clicking a notification opens.
+enum Target {
+    Article {
+        feed_url: String,
+        id: String,
+        url: String,
+    },
+    Reader,
+}
+
+thread_local! {
+    static TARGET_MAP: RefCell<HashMap<String, Target>> = RefCell::new(HashMap::new());
+    static NEXT_ID: RefCell<u32> = RefCell::new(0);
+}
+
+/// Raises notifications for articles in `state` which were not in
+/// `old_state`, following each feed's `NotificationMode`.
+///
+/// Newly subscribed feeds are skipped; their whole backlog would be "new".
+pub fn notify_new_articles(old_state: &State, state: &State) {
+    for (url, feed) in &state.feed_map {
+        if feed.notifications == NotificationMode::Off {
+            continue;
+        }
+        let old_feed = match old_state.feed_map.get(url) {
+            Some(old_feed) => old_feed,
+            None => continue,
+        };
+        let mut new_articles: Vec<(&String, &Article)> = feed
+            .article_map
+            .iter()
+            .filter(|(id, a)| !a.read && !old_feed.article_map.contains_key(*id))
+            .collect();
+        if new_articles.is_empty() {
+            continue;
+        }
+        new_articles.sort_by(|(_, a), (_, b)| b.date.cmp(&a.date));
+
+        let title = feed.display_title();
+        let is_digest = feed.notifications == NotificationMode::Digest
+            || new_articles.len() > MAX_ITEM_NOTIFICATIONS;
+        if is_digest {
+            let titles: Vec<&str> = new_articles
+                .iter()
+                .take(DIGEST_TITLES)
+                .map(|(_, a)| a.title.as_str())
+                .collect();
+            let heading = format!("{} new articles in {}", new_articles.len(), title);
+            create(&heading, &titles.join("\n"), Target::Reader);
+            continue;
+        }
+        for (id, article) in new_articles {
+            let target = Target::Article {
+                feed_url: url.clone(),
+                id: id.clone(),
+                url: article.url.clone(),
+            };
+            create(title, &article.title, target);
+        }
+    }
+}
+
+fn create(title: &str, message: &str, target: Target) {
+    let id = NEXT_ID.with(|next_id| {
+        let mut next_id = next_id.borrow_mut();
+        *next_id += 1;
+        format!("wino-{}", next_id)
+    });
+    let options = json!({
+        "type": "basic",
+        "iconUrl": ICON_URL,
+        "title": title,
+        "message": message,
+    });
+    chrome
+        .notifications()
+        .create(&id, &JsValue::from_serde(&options).unwrap());
+    TARGET_MAP.with(|target_map| target_map.borrow_mut().insert(id, target));
+}
+
+/// Opens what a clicked notification points at, passing `Action::OpenArticle`
+/// to `dispatch` so the article is marked read.
+pub fn listen<F>(dispatch: F)
+where
+    F: Fn(Action) + 'static,
+{
+    let closure = Closure::wrap(Box::new(move |id: String| {
+        let target = TARGET_MAP.with(|target_map| target_map.borrow_mut().remove(&id));
+        chrome.notifications().clear(&id);
+        match target {
+            Some(Target::Article { feed_url, id, url }) => {
+                open_tab(&url);
+                dispatch(Action::OpenArticle(feed_url, id));
+            }
+            Some(Target::Reader) => open_tab(READER_URL),
+            None => {}
+        }
+    }) as Box<FnMut(_)>);
+    chrome
+        .notifications()
+        .on_clicked()
+        .add_listener(closure.as_ref().unchecked_ref());
+    closure.forget();
+
+    // Notifications dismissed or timed out never get clicked.
+    let closure = Closure::wrap(Box::new(|id: String| {
+        TARGET_MAP.with(|target_map| target_map.borrow_mut().remove(&id));
+    }) as Box<FnMut(_)>);
+    chrome
+        .notifications()
+        .on_closed()
+        .add_listener(closure.as_ref().unchecked_ref());
+    closure.forget();
+}
+
+fn open_tab(url: &str) {
+    chrome
+        .tabs()
+        .create(&JsValue::from_serde(&json!({ "url": url })).unwrap());
+}
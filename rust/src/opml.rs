@@ -0,0 +1,222 @@
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Error, Reader, Writer};
+use state::Feed;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outline {
+    pub title: String,
+    pub xml_url: String,
+    pub html_url: String,
+    pub folder: Option<String>,
+}
+
+/// Serializes the subscription list as OPML 2.0, one level of `<outline>`
+/// per folder.
+pub fn export<'a, I>(feeds: I) -> String
+where
+    I: Iterator<Item = &'a Feed>,
+{
+    let mut folder_map: BTreeMap<Option<&str>, Vec<&Feed>> = BTreeMap::new();
+    for feed in feeds {
+        folder_map
+            .entry(feed.folder.as_ref().map(String::as_str))
+            .or_insert_with(Vec::new)
+            .push(feed);
+    }
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    write_document(&mut writer, &folder_map).unwrap();
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+fn write_document(
+    writer: &mut Writer<Vec<u8>>,
+    folder_map: &BTreeMap<Option<&str>, Vec<&Feed>>,
+) -> Result<(), Error> {
+    writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+
+    let mut opml = BytesStart::borrowed_name(b"opml");
+    opml.push_attribute(("version", "2.0"));
+    writer.write_event(Event::Start(opml))?;
+
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"head")))?;
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"title")))?;
+    writer.write_event(Event::Text(BytesText::from_plain_str("wino subscriptions")))?;
+    writer.write_event(Event::End(BytesEnd::borrowed(b"title")))?;
+    writer.write_event(Event::End(BytesEnd::borrowed(b"head")))?;
+
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"body")))?;
+    for (folder, feeds) in folder_map {
+        match folder {
+            Some(folder) => {
+                let mut outline = BytesStart::borrowed_name(b"outline");
+                outline.push_attribute(("text", *folder));
+                outline.push_attribute(("title", *folder));
+                writer.write_event(Event::Start(outline))?;
+                write_feeds(writer, feeds)?;
+                writer.write_event(Event::End(BytesEnd::borrowed(b"outline")))?;
+            }
+            None => write_feeds(writer, feeds)?,
+        }
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"body")))?;
+
+    writer.write_event(Event::End(BytesEnd::borrowed(b"opml")))?;
+    Ok(())
+}
+
+fn write_feeds(writer: &mut Writer<Vec<u8>>, feeds: &[&Feed]) -> Result<(), Error> {
+    for feed in feeds {
+        let mut outline = BytesStart::borrowed_name(b"outline");
+        outline.push_attribute(("type", "rss"));
+        outline.push_attribute(("text", feed.display_title()));
+        outline.push_attribute(("title", feed.display_title()));
+        outline.push_attribute(("xmlUrl", feed.url.as_str()));
+        if !feed.site_url.is_empty() {
+            outline.push_attribute(("htmlUrl", feed.site_url.as_str()));
+        }
+        writer.write_event(Event::Empty(outline))?;
+    }
+    Ok(())
+}
+
+/// Collects every outline carrying an `xmlUrl`, taking the innermost
+/// enclosing outline as its folder.
+pub fn parse(s: &str) -> Result<Vec<Outline>, Error> {
+    let mut reader = Reader::from_str(s);
+    reader.trim_text(true).expand_empty_elements(true);
+
+    let mut outlines = vec![];
+    // One entry per open `<outline>`, `Some` for folders.
+    let mut folder_stack: Vec<Option<String>> = vec![];
+    let mut buf = vec![];
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if e.local_name() == b"outline" => {
+                let mut title = None;
+                let mut text = None;
+                let mut xml_url = None;
+                let mut html_url = None;
+                for attr in e.attributes().with_checks(false) {
+                    let attr = attr?;
+                    let value = attr.unescape_and_decode_value(&reader)?;
+                    match attr.key {
+                        b"title" => title = Some(value),
+                        b"text" => text = Some(value),
+                        b"xmlUrl" => xml_url = Some(value),
+                        b"htmlUrl" => html_url = Some(value),
+                        _ => {}
+                    }
+                }
+                let title = title.or(text).unwrap_or_default();
+
+                match xml_url {
+                    Some(xml_url) => {
+                        let folder = folder_stack.iter().rev().filter_map(Clone::clone).next();
+                        outlines.push(Outline {
+                            title,
+                            xml_url,
+                            html_url: html_url.unwrap_or_default(),
+                            folder,
+                        });
+                        folder_stack.push(None);
+                    }
+                    None if title.is_empty() => folder_stack.push(None),
+                    None => folder_stack.push(Some(title)),
+                }
+            }
+            Event::End(ref e) if e.local_name() == b"outline" => {
+                folder_stack.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(outlines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schedule::Schedule;
+    use state::{FeedStatus, NotificationMode};
+    use std::collections::HashMap;
+
+    fn feed(url: &str, title: &str, folder: Option<&str>) -> Feed {
+        Feed {
+            title: title.to_string(),
+            custom_title: None,
+            url: url.to_string(),
+            site_url: format!("{}/", url),
+            folder: folder.map(String::from),
+            article_map: HashMap::new(),
+            updated: 0.0,
+            visible: true,
+            status: FeedStatus::default(),
+            schedule: Schedule::default(),
+            refresh_minutes: None,
+            notifications: NotificationMode::Off,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn export_then_parse() {
+        let feeds = vec![
+            feed("https://a.example/feed", "A & B", None),
+            feed("https://c.example/feed", "C", Some("Tech")),
+            feed("https://d.example/feed", "D", Some("News")),
+        ];
+        let mut outlines = parse(&export(feeds.iter())).unwrap();
+        outlines.sort_by(|a, b| a.xml_url.cmp(&b.xml_url));
+
+        let expected: Vec<Outline> = feeds
+            .iter()
+            .map(|f| Outline {
+                title: f.title.clone(),
+                xml_url: f.url.clone(),
+                html_url: f.site_url.clone(),
+                folder: f.folder.clone(),
+            })
+            .collect();
+        assert_eq!(outlines, expected);
+    }
+
+    #[test]
+    fn parse_nested_folders() {
+        let document = r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <body>
+    <outline text="News">
+      <outline text="Tech">
+        <outline type="rss" text="C" xmlUrl="https://c.example/feed"/>
+      </outline>
+      <outline type="rss" title="D" text="ignored" xmlUrl="https://d.example/feed" htmlUrl="https://d.example/"/>
+    </outline>
+    <outline>
+      <outline type="rss" text="E" xmlUrl="https://e.example/feed"/>
+    </outline>
+    <outline type="rss" text="A" xmlUrl="https://a.example/feed"/>
+  </body>
+</opml>"#;
+        let outlines = parse(document).unwrap();
+        let folders: Vec<(&str, Option<&str>)> = outlines
+            .iter()
+            .map(|o| (o.title.as_str(), o.folder.as_ref().map(String::as_str)))
+            .collect();
+        assert_eq!(
+            folders,
+            vec![
+                ("C", Some("Tech")),
+                ("D", Some("News")),
+                ("E", None),
+                ("A", None),
+            ]
+        );
+        assert_eq!(outlines[1].html_url, "https://d.example/");
+    }
+}
@@ -0,0 +1,205 @@
+use quick_xml::events::attributes::Attributes;
+use quick_xml::events::Event;
+use quick_xml::{Error as XmlError, Reader};
+use std::io::BufRead;
+
+/// An RSS 1.0 document, whose `item`s are siblings of `channel` under
+/// `rdf:RDF`.
+#[derive(Debug, Default)]
+pub struct Channel {
+    pub title: String,
+    pub link: String,
+    pub update_period: Option<String>,
+    pub update_frequency: Option<String>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Default)]
+pub struct Item {
+    pub about: Option<String>,
+    pub title: String,
+    pub link: String,
+    pub date: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NotRdf,
+    Xml,
+}
+
+impl From<XmlError> for Error {
+    fn from(_: XmlError) -> Error {
+        Error::Xml
+    }
+}
+
+pub fn parse(s: &str) -> Result<Channel, Error> {
+    let mut reader = Reader::from_str(s);
+    reader.trim_text(true).expand_empty_elements(true);
+    let mut buf = vec![];
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) if e.local_name() == b"RDF" => break,
+            Event::Start(_) | Event::Eof => return Err(Error::NotRdf),
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let mut channel = Channel::default();
+    loop {
+        buf.clear();
+        let (name, about) = match reader.read_event(&mut buf)? {
+            Event::Start(ref e) => (e.name().to_vec(), about_attribute(&reader, e.attributes())?),
+            Event::End(_) | Event::Eof => break,
+            _ => continue,
+        };
+        match local_name(&name) {
+            b"channel" => read_channel(&mut reader, &mut channel)?,
+            b"item" => channel.items.push(read_item(&mut reader, about)?),
+            _ => reader.read_to_end(&name, &mut vec![])?,
+        }
+    }
+
+    Ok(channel)
+}
+
+fn read_channel<R: BufRead>(reader: &mut Reader<R>, channel: &mut Channel) -> Result<(), Error> {
+    let mut buf = vec![];
+    loop {
+        buf.clear();
+        let name = match reader.read_event(&mut buf)? {
+            Event::Start(ref e) => e.name().to_vec(),
+            Event::End(_) | Event::Eof => return Ok(()),
+            _ => continue,
+        };
+        match local_name(&name) {
+            b"title" => channel.title = element_text(reader)?,
+            b"link" => channel.link = element_text(reader)?,
+            b"updatePeriod" => channel.update_period = Some(element_text(reader)?),
+            b"updateFrequency" => channel.update_frequency = Some(element_text(reader)?),
+            _ => reader.read_to_end(&name, &mut vec![])?,
+        }
+    }
+}
+
+fn read_item<R: BufRead>(reader: &mut Reader<R>, about: Option<String>) -> Result<Item, Error> {
+    let mut item = Item {
+        about,
+        ..Default::default()
+    };
+    let mut buf = vec![];
+    loop {
+        buf.clear();
+        let name = match reader.read_event(&mut buf)? {
+            Event::Start(ref e) => e.name().to_vec(),
+            Event::End(_) | Event::Eof => return Ok(item),
+            _ => continue,
+        };
+        match local_name(&name) {
+            b"title" => item.title = element_text(reader)?,
+            b"link" => item.link = element_text(reader)?,
+            b"date" => item.date = Some(element_text(reader)?),
+            _ => reader.read_to_end(&name, &mut vec![])?,
+        }
+    }
+}
+
+fn about_attribute<R: BufRead>(
+    reader: &Reader<R>,
+    mut attributes: Attributes,
+) -> Result<Option<String>, Error> {
+    for attr in attributes.with_checks(false) {
+        let attr = attr?;
+        if local_name(attr.key) == b"about" {
+            return Ok(Some(attr.unescape_and_decode_value(reader)?));
+        }
+    }
+    Ok(None)
+}
+
+fn element_text<R: BufRead>(reader: &mut Reader<R>) -> Result<String, Error> {
+    let mut text = String::new();
+    let mut buf = vec![];
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) => reader.read_to_end(e.name(), &mut vec![])?,
+            Event::Text(ref e) => text = e.unescape_and_decode(reader)?,
+            Event::CData(ref e) => text = reader.decode(e).into_owned(),
+            Event::End(_) | Event::Eof => return Ok(text.trim().to_string()),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+fn local_name(name: &[u8]) -> &[u8] {
+    match name.iter().position(|b| *b == b':') {
+        Some(i) => &name[i + 1..],
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rdf:RDF
+  xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+  xmlns="http://purl.org/rss/1.0/"
+  xmlns:dc="http://purl.org/dc/elements/1.1/"
+  xmlns:sy="http://purl.org/rss/1.0/modules/syndication/">
+  <channel rdf:about="https://example.com/">
+    <title>Example</title>
+    <link>https://example.com/</link>
+    <sy:updatePeriod>hourly</sy:updatePeriod>
+    <sy:updateFrequency>2</sy:updateFrequency>
+    <items>
+      <rdf:Seq>
+        <rdf:li rdf:resource="https://example.com/1"/>
+      </rdf:Seq>
+    </items>
+  </channel>
+  <item rdf:about="https://example.com/1">
+    <title>First &amp; foremost</title>
+    <link>https://example.com/1</link>
+    <dc:date>2019-03-01T12:00:00+09:00</dc:date>
+  </item>
+  <item>
+    <title><![CDATA[Second]]></title>
+    <link>https://example.com/2</link>
+  </item>
+</rdf:RDF>"#;
+
+    #[test]
+    fn parse_channel_and_items() {
+        let channel = parse(DOCUMENT).unwrap();
+        assert_eq!(channel.title, "Example");
+        assert_eq!(channel.link, "https://example.com/");
+        assert_eq!(channel.update_period.as_ref().unwrap(), "hourly");
+        assert_eq!(channel.update_frequency.as_ref().unwrap(), "2");
+        assert_eq!(channel.items.len(), 2);
+
+        let first = &channel.items[0];
+        assert_eq!(first.about.as_ref().unwrap(), "https://example.com/1");
+        assert_eq!(first.title, "First & foremost");
+        assert_eq!(first.link, "https://example.com/1");
+        assert_eq!(first.date.as_ref().unwrap(), "2019-03-01T12:00:00+09:00");
+
+        let second = &channel.items[1];
+        assert_eq!(second.about, None);
+        assert_eq!(second.title, "Second");
+        assert_eq!(second.date, None);
+    }
+
+    #[test]
+    fn parse_rejects_other_documents() {
+        match parse("<rss version=\"2.0\"><channel></channel></rss>") {
+            Err(Error::NotRdf) => {}
+            result => panic!("expected NotRdf, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+const MINUTE: f64 = 60.0 * 1000.0;
+const HOUR: f64 = 60.0 * MINUTE;
+
+const WEEKDAYS: &[&str] = &[
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// How often a feed asks to be polled, from RSS `<ttl>`, `<skipHours>` and
+/// `<skipDays>` and the syndication module's `updatePeriod` and
+/// `updateFrequency`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub ttl_minutes: Option<u32>,
+    pub update_minutes: Option<u32>,
+    /// Hours of the day in UTC, 0 to 23.
+    pub skip_hours: Vec<u32>,
+    /// Days of the week in UTC, 0 for Monday.
+    pub skip_days: Vec<u32>,
+}
+
+impl Schedule {
+    pub fn new(
+        ttl: Option<&str>,
+        update_period: Option<&str>,
+        update_frequency: Option<&str>,
+        skip_hours: &[String],
+        skip_days: &[String],
+    ) -> Schedule {
+        Schedule {
+            ttl_minutes: ttl.and_then(|ttl| ttl.trim().parse().ok()),
+            update_minutes: update_minutes(update_period, update_frequency),
+            skip_hours: skip_hours
+                .iter()
+                .filter_map(|hour| hour.trim().parse::<u32>().ok())
+                .map(|hour| hour % 24)
+                .collect(),
+            skip_days: skip_days
+                .iter()
+                .filter_map(|day| {
+                    WEEKDAYS
+                        .iter()
+                        .position(|d| d.eq_ignore_ascii_case(day.trim()))
+                })
+                .map(|day| day as u32)
+                .collect(),
+        }
+    }
+
+    /// The longer of the intervals the feed gives, if it gives any.
+    pub fn interval_minutes(&self) -> Option<u32> {
+        match (self.ttl_minutes, self.update_minutes) {
+            (Some(ttl), Some(update)) => Some(ttl.max(update)),
+            (ttl, update) => ttl.or(update),
+        }
+    }
+
+    /// Moves `time` forward to the start of the first hour the feed does not
+    /// ask to be skipped.
+    pub fn skip(&self, time: f64) -> f64 {
+        let mut time = time;
+        // A week of skipped hours would loop forever; give up after it.
+        for _ in 0..24 * 7 {
+            let date = Utc.timestamp_millis(time as i64);
+            let skipped = self.skip_hours.contains(&date.hour())
+                || self
+                    .skip_days
+                    .contains(&date.weekday().num_days_from_monday());
+            if !skipped {
+                break;
+            }
+            let into_hour = f64::from(date.minute()) * MINUTE + f64::from(date.second()) * 1000.0;
+            time = time - into_hour + HOUR;
+        }
+        time
+    }
+}
+
+/// `updatePeriod` defaults to daily and `updateFrequency` to once per period.
+fn update_minutes(period: Option<&str>, frequency: Option<&str>) -> Option<u32> {
+    if period.is_none() && frequency.is_none() {
+        return None;
+    }
+    let period_minutes = match period.map(str::trim).unwrap_or("daily") {
+        "hourly" => 60,
+        "weekly" => 60 * 24 * 7,
+        "monthly" => 60 * 24 * 30,
+        "yearly" => 60 * 24 * 365,
+        _ => 60 * 24,
+    };
+    let frequency = frequency
+        .and_then(|f| f.trim().parse::<u32>().ok())
+        .filter(|f| *f > 0)
+        .unwrap_or(1);
+    Some(period_minutes / frequency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> f64 {
+        Utc.ymd(y, m, d).and_hms(h, min, 0).timestamp_millis() as f64
+    }
+
+    #[test]
+    fn skip_hours() {
+        let schedule = Schedule {
+            skip_hours: vec![0, 1],
+            ..Default::default()
+        };
+        // 2019-03-01 is a Friday.
+        assert_eq!(schedule.skip(at(2019, 3, 1, 0, 30)), at(2019, 3, 1, 2, 0));
+        assert_eq!(schedule.skip(at(2019, 3, 1, 2, 30)), at(2019, 3, 1, 2, 30));
+    }
+
+    #[test]
+    fn skip_days() {
+        let schedule = Schedule::new(
+            None,
+            None,
+            None,
+            &[],
+            &["Saturday".to_string(), " sunday ".to_string()],
+        );
+        assert_eq!(schedule.skip_days, vec![5, 6]);
+        assert_eq!(schedule.skip(at(2019, 3, 2, 10, 15)), at(2019, 3, 4, 0, 0));
+    }
+
+    #[test]
+    fn skip_hours_and_days() {
+        let schedule = Schedule::new(
+            None,
+            None,
+            None,
+            &["23".to_string(), "0".to_string()],
+            &["Sunday".to_string()],
+        );
+        assert_eq!(schedule.skip(at(2019, 3, 2, 23, 10)), at(2019, 3, 4, 1, 0));
+    }
+
+    #[test]
+    fn skip_gives_up_when_everything_is_skipped() {
+        let schedule = Schedule {
+            skip_hours: (0..24).collect(),
+            ..Default::default()
+        };
+        let time = at(2019, 3, 1, 0, 0);
+        assert_eq!(schedule.skip(time), time + 7.0 * 24.0 * HOUR);
+    }
+
+    #[test]
+    fn update_minutes_defaults() {
+        assert_eq!(update_minutes(None, None), None);
+        assert_eq!(update_minutes(Some("hourly"), None), Some(60));
+        assert_eq!(update_minutes(None, Some("2")), Some(12 * 60));
+        assert_eq!(update_minutes(Some("weekly"), Some("0")), Some(7 * 24 * 60));
+        assert_eq!(
+            update_minutes(Some("fortnightly"), Some("x")),
+            Some(24 * 60)
+        );
+    }
+
+    #[test]
+    fn interval_is_the_longer_one() {
+        let schedule = Schedule::new(Some("90"), Some("hourly"), None, &[], &[]);
+        assert_eq!(schedule.interval_minutes(), Some(90));
+        assert_eq!(Schedule::default().interval_minutes(), None);
+    }
+}
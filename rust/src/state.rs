@@ -1,17 +1,62 @@
-use atom_syndication::{Entry, Feed as AtomFeed};
+use atom_syndication::{Entry, Error as AtomError, Feed as AtomFeed};
+use backup::PendingImport;
 use chrono::{DateTime, FixedOffset};
-use js_sys::Date;
+use date;
+use discovery::Candidate;
+use fetch;
+use json_feed::{self, JsonFeed};
+use js_sys::{Date, Math};
+use rdf;
 use rss::extension::dublincore::DublinCoreExtension;
-use rss::{Channel, Item};
+use rss::extension::ExtensionMap;
+use rss::{Channel, Error as RssError, Item};
+use schedule::Schedule;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry as MapEntry;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Polling interval for feeds which give none and have no user override.
+const DEFAULT_REFRESH_MINUTES: u32 = 30;
+/// Bounds on the intervals feeds ask for.
+const MIN_REFRESH_MINUTES: u32 = 5;
+const MAX_REFRESH_MINUTES: u32 = 24 * 60;
+
+/// Least a failing feed's retries start from; they start from its refresh
+/// interval when that is longer, and double on every failure up to the
+/// maximum.
+const RETRY_BASE_MINUTES: f64 = 5.0;
+const RETRY_MAX_MINUTES: f64 = 24.0 * 60.0;
+
+/// Fetches in a row redirected to the same URL before a feed is moved there.
+const REDIRECTS_BEFORE_MOVE: u32 = 3;
 
 #[derive(Deserialize, Clone, Debug, PartialEq, Serialize)]
 #[serde(default)]
 pub struct State {
+    #[serde(skip)]
     pub new_feed_url: String,
+    #[serde(skip)]
     pub is_loading_new_feed: bool,
+    #[serde(skip)]
+    pub preview_feed: Option<Feed>,
+    #[serde(skip)]
     pub is_opening_sidebar: bool,
+    pub hide_read: bool,
+    pub is_showing_starred: bool,
+    pub older_than_days: u32,
+    pub remove_gone_feeds: bool,
+    pub fetch_concurrency: usize,
+    #[serde(skip)]
+    pub notice: Option<String>,
+    #[serde(skip)]
+    pub pending_import: Option<PendingImport>,
+    #[serde(skip)]
+    pub discovered_feeds: Vec<Candidate>,
+    /// Articles which arrived while the page was hidden.
+    #[serde(skip)]
+    pub unseen_count: usize,
     pub feed_map: HashMap<String, Feed>,
 }
 
@@ -20,35 +65,291 @@ impl Default for State {
         State {
             new_feed_url: String::new(),
             is_loading_new_feed: false,
+            preview_feed: None,
             is_opening_sidebar: false,
+            hide_read: false,
+            is_showing_starred: false,
+            older_than_days: 7,
+            remove_gone_feeds: false,
+            fetch_concurrency: fetch::DEFAULT_CONCURRENCY,
+            notice: None,
+            pending_import: None,
+            discovered_feeds: vec![],
+            unseen_count: 0,
             feed_map: HashMap::new(),
         }
     }
 }
 
+impl State {
+    /// Re-keys the feed at `from` to `to`, merging it into any feed already
+    /// subscribed there.
+    pub fn move_feed(&mut self, from: &str, to: String) {
+        let mut feed = match self.feed_map.remove(from) {
+            Some(feed) => feed,
+            None => return,
+        };
+        feed.url = to.clone();
+        feed.status.clear_redirect();
+        match self.feed_map.entry(to) {
+            MapEntry::Occupied(e) => e.into_mut().merge_copy(feed),
+            MapEntry::Vacant(e) => {
+                e.insert(feed);
+            }
+        }
+    }
+
+    pub fn get_article(&self, feed_url: &str, id: &str) -> Option<&Article> {
+        self.feed_map
+            .get(feed_url)
+            .and_then(|feed| feed.article_map.get(id))
+    }
+
+    pub fn get_article_mut(&mut self, feed_url: &str, id: &str) -> Option<&mut Article> {
+        self.feed_map
+            .get_mut(feed_url)
+            .and_then(|feed| feed.article_map.get_mut(id))
+    }
+
+    pub fn unread_count(&self) -> usize {
+        self.feed_map.values().map(Feed::unread_count).sum()
+    }
+
+    /// Counts articles which are not in `old`. Feeds `old` has not
+    /// subscribed to are left out, their backlog is not news.
+    pub fn new_article_count(&self, old: &State) -> usize {
+        self.feed_map
+            .iter()
+            .map(|(url, feed)| match old.feed_map.get(url) {
+                Some(old_feed) => feed
+                    .article_map
+                    .keys()
+                    .filter(|id| !old_feed.article_map.contains_key(*id))
+                    .count(),
+                None => 0,
+            })
+            .sum()
+    }
+
+    pub fn starred_count(&self) -> usize {
+        self.feed_map
+            .values()
+            .flat_map(|feed| feed.article_map.values())
+            .filter(|a| a.starred)
+            .count()
+    }
+
+    /// Articles shown in the main column as `(feed_url, feed, id, article)`,
+    /// newest first.
+    ///
+    /// The starred view shows every starred article regardless of feed
+    /// visibility and read state.
+    pub fn visible_articles(&self) -> Vec<(&str, &Feed, &str, &Article)> {
+        let hide_read = self.hide_read;
+        let is_showing_starred = self.is_showing_starred;
+        let mut article_vec: Vec<_> = self
+            .feed_map
+            .iter()
+            .filter(|(_, feed)| is_showing_starred || feed.visible)
+            .flat_map(|(feed_url, feed)| {
+                feed.article_map
+                    .iter()
+                    .filter(move |(_, article)| {
+                        if is_showing_starred {
+                            return article.starred;
+                        }
+                        !hide_read || !article.read
+                    })
+                    .map(move |(id, article)| (feed_url.as_str(), feed, id.as_str(), article))
+            })
+            .collect();
+        article_vec.sort_by(|(_, _, _, a), (_, _, _, b)| b.date.cmp(&a.date));
+        article_vec
+    }
+
+    pub fn mark_visible_read(&mut self) {
+        let keys: Vec<(String, String)> = self
+            .visible_articles()
+            .into_iter()
+            .map(|(feed_url, _, id, _)| (feed_url.to_string(), id.to_string()))
+            .collect();
+        for (feed_url, id) in keys {
+            if let Some(article) = self.get_article_mut(&feed_url, &id) {
+                article.read = true;
+            }
+        }
+    }
+
+    pub fn mark_older_than_read(&mut self, cutoff: DateTime<FixedOffset>) {
+        let articles = self
+            .feed_map
+            .values_mut()
+            .flat_map(|feed| feed.article_map.values_mut());
+        for article in articles.filter(|a| a.date < cutoff) {
+            article.read = true;
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Feed {
     pub title: String,
+    pub custom_title: Option<String>,
     pub url: String,
+    pub site_url: String,
+    pub folder: Option<String>,
     pub article_map: HashMap<String, Article>,
     pub updated: f64,
     pub visible: bool,
+    pub status: FeedStatus,
+    pub schedule: Schedule,
+    /// Polling interval set by the user, overriding `schedule`.
+    pub refresh_minutes: Option<u32>,
+    pub notifications: NotificationMode,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
 }
 
 impl Default for Feed {
     fn default() -> Self {
         Feed {
             title: String::default(),
+            custom_title: None,
             url: String::default(),
+            site_url: String::default(),
+            folder: None,
             article_map: HashMap::default(),
             updated: Date::now(),
             visible: true,
+            status: FeedStatus::default(),
+            schedule: Schedule::default(),
+            refresh_minutes: None,
+            notifications: NotificationMode::Off,
+            etag: None,
+            last_modified: None,
         }
     }
 }
 
 impl Feed {
+    pub fn display_title(&self) -> &str {
+        self.custom_title.as_ref().unwrap_or(&self.title)
+    }
+
+    pub fn unread_count(&self) -> usize {
+        self.article_map.values().filter(|a| !a.read).count()
+    }
+
+    pub fn refresh_interval(&self) -> u32 {
+        self.refresh_minutes.unwrap_or_else(|| {
+            self.schedule
+                .interval_minutes()
+                .map_or(DEFAULT_REFRESH_MINUTES, |minutes| {
+                    minutes.max(MIN_REFRESH_MINUTES).min(MAX_REFRESH_MINUTES)
+                })
+        })
+    }
+
+    /// When to poll next after a fetch finishing at `now`.
+    pub fn next_fetch_after(&self, now: f64) -> f64 {
+        let interval = f64::from(self.refresh_interval()) * 60.0 * 1000.0;
+        self.schedule.skip(now + interval)
+    }
+
+    pub fn is_due(&self, now: f64) -> bool {
+        !self.status.dead && self.status.next_fetch <= now
+    }
+
+    pub fn newest_articles(&self) -> Vec<&Article> {
+        let mut articles: Vec<_> = self.article_map.values().collect();
+        articles.sort_by(|a, b| b.date.cmp(&a.date));
+        articles
+    }
+
+    pub fn mark_all_read(&mut self) {
+        for article in self.article_map.values_mut() {
+            article.read = true;
+        }
+    }
+
+    /// Merges a freshly fetched copy of this feed into it, keeping the
+    /// articles which dropped off the feed window and the user's settings.
+    pub fn merge(&mut self, fetched: Feed) {
+        self.title = fetched.title;
+        self.schedule = fetched.schedule;
+        if !fetched.site_url.is_empty() {
+            self.site_url = fetched.site_url;
+        }
+        self.updated = fetched.updated;
+        for (id, article) in fetched.article_map {
+            match self.article_map.entry(id) {
+                MapEntry::Occupied(mut e) => e.get_mut().merge(article),
+                MapEntry::Vacant(e) => {
+                    e.insert(article);
+                }
+            }
+        }
+    }
+
+    /// Merges another copy of this feed, e.g. from a backup made on another
+    /// machine. The newer copy of each article wins, read and star flags are
+    /// kept from both sides and this copy's settings are left alone.
+    pub fn merge_copy(&mut self, other: Feed) {
+        if other.updated > self.updated {
+            self.title = other.title;
+            self.site_url = other.site_url;
+            self.schedule = other.schedule;
+            self.updated = other.updated;
+        }
+        for (id, article) in other.article_map {
+            match self.article_map.entry(id) {
+                MapEntry::Occupied(mut e) => e.get_mut().merge_copy(article),
+                MapEntry::Vacant(e) => {
+                    e.insert(article);
+                }
+            }
+        }
+    }
+
+    /// Whether the user's settings differ between two copies of a feed.
+    pub fn conflicts_with(&self, other: &Feed) -> bool {
+        self.custom_title != other.custom_title
+            || self.folder != other.folder
+            || self.visible != other.visible
+            || self.refresh_minutes != other.refresh_minutes
+            || self.notifications != other.notifications
+    }
+
+    pub fn parse(url: String, content_type: Option<&str>, body: &str) -> Result<Self, FeedError> {
+        if json_feed::is_json_feed(content_type, body) {
+            let json_feed: JsonFeed =
+                serde_json::from_str(body).map_err(|_| FeedError::JsonParse)?;
+            if !json_feed.version.starts_with("https://jsonfeed.org/version/1") {
+                return Err(FeedError::UnknownFormat);
+            }
+            return Ok(Feed::from_json_feed(url, &json_feed));
+        }
+
+        match rdf::parse(body) {
+            Ok(channel) => return Ok(Feed::from_rdf(url, &channel)),
+            Err(rdf::Error::NotRdf) => {}
+            Err(_) => return Err(FeedError::XmlParse),
+        }
+
+        match AtomFeed::from_str(body) {
+            Ok(atom) => return Ok(Feed::from_atom(url, &atom)),
+            Err(AtomError::InvalidStartTag) | Err(AtomError::Eof) => {}
+            Err(_) => return Err(FeedError::XmlParse),
+        }
+
+        match Channel::from_str(body) {
+            Ok(channel) => Ok(Feed::from_rss(url, &channel)),
+            Err(RssError::InvalidStartTag) | Err(RssError::Eof) => Err(FeedError::UnknownFormat),
+            Err(_) => Err(FeedError::XmlParse),
+        }
+    }
+
     pub fn from_atom(url: String, atom: &AtomFeed) -> Self {
         let mut article_map = HashMap::new();
 
@@ -57,10 +358,69 @@ impl Feed {
             article_map.insert(id.to_string(), Article::from_atom(entry));
         }
 
+        let site_url = atom
+            .links()
+            .iter()
+            .find(|link| link.rel() == "alternate")
+            .map_or("", |link| link.href())
+            .to_string();
+
+        let sy = |name| {
+            atom.extensions()
+                .get("sy")
+                .and_then(|sy| sy.get(name))
+                .and_then(|values| values.get(0))
+                .and_then(|value| value.value())
+        };
+        let schedule = Schedule::new(None, sy("updatePeriod"), sy("updateFrequency"), &[], &[]);
+
         Feed {
             article_map,
             url,
+            site_url,
             title: atom.title().to_string(),
+            schedule,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json_feed(url: String, json_feed: &JsonFeed) -> Self {
+        let mut article_map = HashMap::new();
+        for item in &json_feed.items {
+            let article = Article::from_json_feed(item);
+            let id = item.id().unwrap_or_else(|| article.url.clone());
+            article_map.insert(id, article);
+        }
+
+        Feed {
+            article_map,
+            url,
+            site_url: json_feed.home_page_url.clone().unwrap_or_default(),
+            title: json_feed.title.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_rdf(url: String, channel: &rdf::Channel) -> Self {
+        let mut article_map = HashMap::new();
+        for item in &channel.items {
+            let article = Article::from_rdf(item);
+            let id = item.about.clone().unwrap_or_else(|| article.url.clone());
+            article_map.insert(id, article);
+        }
+
+        Feed {
+            article_map,
+            url,
+            site_url: channel.link.clone(),
+            title: channel.title.clone(),
+            schedule: Schedule::new(
+                None,
+                channel.update_period.as_ref().map(String::as_str),
+                channel.update_frequency.as_ref().map(String::as_str),
+                &[],
+                &[],
+            ),
             ..Default::default()
         }
     }
@@ -78,22 +438,229 @@ impl Feed {
         Feed {
             article_map,
             url,
+            site_url: channel.link().to_string(),
             title: channel.title().to_string(),
+            schedule: Schedule::new(
+                channel.ttl(),
+                rss_extension_value(channel.extensions(), "sy", "updatePeriod"),
+                rss_extension_value(channel.extensions(), "sy", "updateFrequency"),
+                channel.skip_hours(),
+                channel.skip_days(),
+            ),
             ..Default::default()
         }
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FeedStatus {
+    pub last_success: Option<f64>,
+    pub last_error: Option<FeedError>,
+    /// Set once the feed answers 410 Gone; dead feeds are not polled.
+    pub dead: bool,
+    pub redirected_to: Option<String>,
+    pub redirect_count: u32,
+    pub next_fetch: f64,
+    /// Failed fetches since the last success.
+    pub failures: u32,
+}
+
+impl FeedStatus {
+    pub fn record_success(&mut self, now: f64) {
+        self.last_success = Some(now);
+        self.last_error = None;
+        self.dead = false;
+        self.failures = 0;
+    }
+
+    /// Counts a failed fetch and puts the next attempt off exponentially
+    /// from `interval_minutes`, the feed's usual refresh interval, so a
+    /// failing feed is never polled more often than a working one. Jitter
+    /// spreads out feeds failing together.
+    pub fn record_failure(&mut self, error: FeedError, interval_minutes: u32, now: f64) {
+        self.failures += 1;
+        let start = f64::from(interval_minutes).max(RETRY_BASE_MINUTES);
+        let exponent = self.failures.min(16) as i32;
+        // Half of the backoff is jitter, so it must reach twice the start.
+        let max = RETRY_MAX_MINUTES.max(2.0 * start);
+        let backoff = (start * 2f64.powi(exponent)).min(max) * 60.0 * 1000.0;
+        let mut delay = backoff / 2.0 + Math::random() * backoff / 2.0;
+        if let FeedError::RateLimited(Some(seconds)) = error {
+            delay = delay.max(f64::from(seconds) * 1000.0);
+        }
+        self.dead = error == FeedError::Gone;
+        self.last_error = Some(error);
+        self.next_fetch = now + delay;
+    }
+
+    pub fn is_retrying(&self) -> bool {
+        self.failures > 0 && !self.dead
+    }
+
+    /// Counts a fetch which ended up at `url`, and tells whether the redirect
+    /// has held long enough to be taken as permanent.
+    ///
+    /// `fetch` follows redirects without exposing their status, so 301/308
+    /// cannot be told apart from a temporary 302/307 directly.
+    pub fn record_redirect(&mut self, url: &str) -> bool {
+        if self.redirected_to.as_ref().map(String::as_str) == Some(url) {
+            self.redirect_count += 1;
+        } else {
+            self.redirected_to = Some(url.to_string());
+            self.redirect_count = 1;
+        }
+        self.redirect_count >= REDIRECTS_BEFORE_MOVE
+    }
+
+    pub fn clear_redirect(&mut self) {
+        self.redirected_to = None;
+        self.redirect_count = 0;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum FeedError {
+    Network,
+    Timeout,
+    NotFound,
+    Gone,
+    RateLimited(Option<u32>),
+    ServerError(u16),
+    HttpStatus(u16),
+    XmlParse,
+    JsonParse,
+    UnknownFormat,
+}
+
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedError::Network => write!(f, "network error"),
+            FeedError::Timeout => write!(f, "timed out"),
+            FeedError::NotFound => write!(f, "not found"),
+            FeedError::Gone => write!(f, "gone"),
+            FeedError::RateLimited(_) => write!(f, "rate limited"),
+            FeedError::ServerError(status) => write!(f, "server error {}", status),
+            FeedError::HttpStatus(status) => write!(f, "HTTP {}", status),
+            FeedError::XmlParse => write!(f, "invalid XML"),
+            FeedError::JsonParse => write!(f, "invalid JSON"),
+            FeedError::UnknownFormat => write!(f, "unknown format"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Article {
     pub title: String,
     pub date: DateTime<FixedOffset>,
+    #[serde(default)]
+    pub date_source: DateSource,
     pub url: String,
+    #[serde(default)]
+    pub first_seen: f64,
+    #[serde(default)]
+    pub last_seen: f64,
+    #[serde(default)]
+    pub read: bool,
+    #[serde(default)]
+    pub starred: bool,
+}
+
+/// Whether new articles of a feed raise desktop notifications.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum NotificationMode {
+    Off,
+    EveryItem,
+    /// One notification per fetch, however many articles it brought.
+    Digest,
+}
+
+impl NotificationMode {
+    pub fn next(&self) -> NotificationMode {
+        match self {
+            NotificationMode::Off => NotificationMode::EveryItem,
+            NotificationMode::EveryItem => NotificationMode::Digest,
+            NotificationMode::Digest => NotificationMode::Off,
+        }
+    }
+}
+
+impl Default for NotificationMode {
+    fn default() -> Self {
+        NotificationMode::Off
+    }
+}
+
+impl fmt::Display for NotificationMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotificationMode::Off => write!(f, "off"),
+            NotificationMode::EveryItem => write!(f, "every article"),
+            NotificationMode::Digest => write!(f, "digest"),
+        }
+    }
+}
+
+/// Where `Article::date` came from.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DateSource {
+    Published,
+    Updated,
+    DublinCore,
+    FirstSeen,
+}
+
+impl Default for DateSource {
+    fn default() -> Self {
+        DateSource::Published
+    }
 }
 
 impl Article {
+    /// Takes the fetched title, URL and date. `last_seen` only moves when one
+    /// of them changed, so an unchanged article is not written again;
+    /// `Feed::updated` tells when the feed was last fetched.
+    fn merge(&mut self, fetched: Article) {
+        // A first-seen date would move forward on every fetch.
+        let keeps_date = fetched.date_source == DateSource::FirstSeen
+            || (self.date == fetched.date && self.date_source == fetched.date_source);
+        if keeps_date && self.title == fetched.title && self.url == fetched.url {
+            return;
+        }
+        self.title = fetched.title;
+        self.url = fetched.url;
+        if fetched.date_source != DateSource::FirstSeen {
+            self.date = fetched.date;
+            self.date_source = fetched.date_source;
+        }
+        self.last_seen = fetched.last_seen;
+    }
+
+    fn merge_copy(&mut self, other: Article) {
+        let read = self.read || other.read;
+        let starred = self.starred || other.starred;
+        let first_seen = self.first_seen.min(other.first_seen);
+        if other.last_seen > self.last_seen {
+            *self = other;
+        }
+        self.read = read;
+        self.starred = starred;
+        self.first_seen = first_seen;
+    }
+
     fn from_atom(entry: &Entry) -> Self {
-        let date = parse_date(entry.published().unwrap_or(""));
+        let dc_date = entry
+            .extensions()
+            .get("dc")
+            .and_then(|dc| dc.get("date"))
+            .and_then(|dates| dates.get(0))
+            .and_then(|date| date.value());
+        let (date, date_source) = resolve_date(&[
+            (entry.published(), DateSource::Published),
+            (Some(entry.updated()), DateSource::Updated),
+            (dc_date, DateSource::DublinCore),
+        ]);
         Article {
             title: entry.title().to_string(),
             url: entry
@@ -102,31 +669,91 @@ impl Article {
                 .map_or("", |link| link.href())
                 .to_string(),
             date,
+            date_source,
+            first_seen: Date::now(),
+            last_seen: Date::now(),
+            read: false,
+            starred: false,
+        }
+    }
+
+    fn from_json_feed(item: &json_feed::Item) -> Self {
+        let (date, date_source) = resolve_date(&[
+            (item.date_published.as_ref().map(String::as_str), DateSource::Published),
+            (item.date_modified.as_ref().map(String::as_str), DateSource::Updated),
+        ]);
+        Article {
+            title: item
+                .title
+                .as_ref()
+                .or_else(|| item.summary.as_ref())
+                .cloned()
+                .unwrap_or_default(),
+            url: item.url().to_string(),
+            date,
+            date_source,
+            first_seen: Date::now(),
+            last_seen: Date::now(),
+            read: false,
+            starred: false,
+        }
+    }
+
+    fn from_rdf(item: &rdf::Item) -> Self {
+        let (date, date_source) = resolve_date(&[(
+            item.date.as_ref().map(String::as_str),
+            DateSource::DublinCore,
+        )]);
+        Article {
+            title: item.title.clone(),
+            url: item.link.clone(),
+            date,
+            date_source,
+            first_seen: Date::now(),
+            last_seen: Date::now(),
+            read: false,
+            starred: false,
         }
     }
 
     fn from_rss(item: &Item) -> Self {
-        let date_str = item
-            .pub_date()
-            .or_else(|| {
-                item.dublin_core_ext()
-                    .map(DublinCoreExtension::dates)
-                    .and_then(|date| date.get(0))
-                    .map(String::as_str)
-            })
-            .unwrap_or("");
-        let date = parse_date(date_str);
+        let dc_date = item
+            .dublin_core_ext()
+            .map(DublinCoreExtension::dates)
+            .and_then(|date| date.get(0))
+            .map(String::as_str);
+        let (date, date_source) = resolve_date(&[
+            (item.pub_date(), DateSource::Published),
+            (dc_date, DateSource::DublinCore),
+        ]);
         let url = item.link().unwrap_or("").to_string();
         Article {
             title: item.title().unwrap_or("").to_string(),
             url: url.clone(),
             date,
+            date_source,
+            first_seen: Date::now(),
+            last_seen: Date::now(),
+            read: false,
+            starred: false,
         }
     }
 }
 
-fn parse_date(s: &str) -> DateTime<FixedOffset> {
-    DateTime::parse_from_rfc3339(s)
-        .or_else(|_| DateTime::parse_from_rfc2822(s))
-        .unwrap()
+fn rss_extension_value<'a>(extensions: &'a ExtensionMap, prefix: &str, name: &str) -> Option<&'a str> {
+    extensions
+        .get(prefix)
+        .and_then(|extension| extension.get(name))
+        .and_then(|values| values.get(0))
+        .and_then(|value| value.value())
+}
+
+/// Takes the first candidate that parses, falling back to the time the
+/// article was first seen.
+fn resolve_date(candidates: &[(Option<&str>, DateSource)]) -> (DateTime<FixedOffset>, DateSource) {
+    candidates
+        .iter()
+        .filter_map(|(s, source)| s.and_then(date::parse).map(|date| (date, source.clone())))
+        .next()
+        .unwrap_or_else(|| (date::now(), DateSource::FirstSeen))
 }
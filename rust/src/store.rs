@@ -0,0 +1,597 @@
+use futures::future::{self, Either, Future};
+use js_sys::{Date, Promise};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use state::{Article, Feed, State};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    console, window, BroadcastChannel, IdbDatabase, IdbKeyRange, IdbObjectStoreParameters,
+    IdbRequest, IdbTransaction, IdbTransactionMode, MessageEvent,
+};
+
+const DB_NAME: &str = "wino";
+const DB_VERSION: u32 = 1;
+
+const SETTINGS_STORE: &str = "settings";
+/// Feeds without their articles, keyed by URL.
+const FEED_STORE: &str = "feeds";
+/// Articles keyed by `[feed_url, id]`.
+const ARTICLE_STORE: &str = "articles";
+const STORE_NAMES: &[&str] = &[SETTINGS_STORE, FEED_STORE, ARTICLE_STORE];
+
+const SETTINGS_KEY: &str = "settings";
+/// Where earlier versions kept the whole state in localStorage.
+const LEGACY_STATE_KEY: &str = "state";
+
+/// Pages and the background script announce what they stored here.
+const CHANNEL_NAME: &str = "wino";
+
+/// Writes wait until no change has come for this long.
+const SAVE_DELAY_MS: i32 = 500;
+/// ...but no longer than this after the first change they hold, so steady
+/// fetching still gets stored.
+const MAX_SAVE_DELAY_MS: f64 = 5000.0;
+
+thread_local! {
+    static DB: RefCell<Option<IdbDatabase>> = RefCell::new(None);
+    static CHANNEL: BroadcastChannel = BroadcastChannel::new(CHANNEL_NAME).unwrap();
+    static PENDING: RefCell<Pending> = RefCell::new(Pending::default());
+    static FLUSH: Closure<Fn()> = Closure::wrap(Box::new(flush) as Box<Fn()>);
+}
+
+/// Changes waiting to be written.
+#[derive(Default)]
+struct Pending {
+    /// The state as last written, which the next write is diffed against.
+    flushed: State,
+    /// The state to write once changes settle.
+    latest: Option<State>,
+    /// When the first change in `latest` came.
+    since: f64,
+    timer: Option<i32>,
+}
+
+impl Pending {
+    /// Takes in what another page stored, so it is neither written again
+    /// nor lost to a stale copy. Returns the change rebased onto the edits
+    /// not written yet, which is what the page should apply.
+    fn sync(&mut self, change: Change) -> Change {
+        let rebased = match self.latest {
+            Some(ref latest) => change.clone().rebase(&self.flushed, latest),
+            None => change.clone(),
+        };
+        change.apply_to(&mut self.flushed);
+        if let Some(ref mut latest) = self.latest {
+            rebased.clone().apply_to(latest);
+        }
+        rebased
+    }
+}
+
+/// The parts of `State` stored apart from feeds. Transient UI, such as the
+/// "Add Feed" input and the sidebar, stays out of it. Fields missing from a
+/// stored record take their defaults.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    hide_read: bool,
+    is_showing_starred: bool,
+    older_than_days: u32,
+    remove_gone_feeds: bool,
+    fetch_concurrency: usize,
+}
+
+impl Settings {
+    fn of(state: &State) -> Settings {
+        Settings {
+            hide_read: state.hide_read,
+            is_showing_starred: state.is_showing_starred,
+            older_than_days: state.older_than_days,
+            remove_gone_feeds: state.remove_gone_feeds,
+            fetch_concurrency: state.fetch_concurrency,
+        }
+    }
+
+    fn apply(self, state: &mut State) {
+        state.hide_read = self.hide_read;
+        state.is_showing_starred = self.is_showing_starred;
+        state.older_than_days = self.older_than_days;
+        state.remove_gone_feeds = self.remove_gone_feeds;
+        state.fetch_concurrency = self.fetch_concurrency;
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::of(&State::default())
+    }
+}
+
+/// What one save wrote, passed on to the other pages: only the records
+/// which changed, so a fetch which brought one new article sends that one.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Change {
+    settings: Option<Settings>,
+    /// Feeds whose own fields changed, without their articles.
+    feeds: Vec<Feed>,
+    /// Articles added or changed.
+    articles: Vec<ArticleRecord>,
+    /// `(feed_url, id)` of articles dropped from feeds which stay.
+    removed_articles: Vec<(String, String)>,
+    /// URLs of removed feeds, whose articles go with them.
+    removed: Vec<String>,
+}
+
+impl Change {
+    fn between(old_state: &State, state: &State) -> Change {
+        let settings = Settings::of(state);
+        let mut change = Change {
+            settings: if settings != Settings::of(old_state) {
+                Some(settings)
+            } else {
+                None
+            },
+            removed: old_state
+                .feed_map
+                .keys()
+                .filter(|url| !state.feed_map.contains_key(*url))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+        for (url, feed) in &state.feed_map {
+            let old_feed = old_state.feed_map.get(url);
+            if old_feed == Some(feed) {
+                continue;
+            }
+            let feed_metadata = metadata(feed);
+            if old_feed.map(metadata).as_ref() != Some(&feed_metadata) {
+                change.feeds.push(feed_metadata);
+            }
+            let old_article_map = old_feed.map(|f| &f.article_map);
+            for (id, article) in &feed.article_map {
+                if old_article_map.and_then(|m| m.get(id)) != Some(article) {
+                    change.articles.push(ArticleRecord {
+                        feed_url: url.clone(),
+                        id: id.clone(),
+                        article: article.clone(),
+                    });
+                }
+            }
+            for id in old_article_map.into_iter().flat_map(HashMap::keys) {
+                if !feed.article_map.contains_key(id) {
+                    change.removed_articles.push((url.clone(), id.clone()));
+                }
+            }
+        }
+        change
+    }
+
+    fn is_empty(&self) -> bool {
+        self.settings.is_none()
+            && self.feeds.is_empty()
+            && self.articles.is_empty()
+            && self.removed_articles.is_empty()
+            && self.removed.is_empty()
+    }
+
+    /// Brings `state` up to date with what another page stored, keeping
+    /// what is only shown here.
+    pub fn apply(self, mut state: State) -> State {
+        self.apply_to(&mut state);
+        state
+    }
+
+    /// Keeps the edits `local` has over `base` which are not written yet:
+    /// settings, per-feed settings and read and star flags. Feeds removed
+    /// in `local` stay removed.
+    fn rebase(mut self, base: &State, local: &State) -> Change {
+        if Settings::of(local) != Settings::of(base) {
+            self.settings = None;
+        }
+        let is_kept =
+            |url: &String| local.feed_map.contains_key(url) || !base.feed_map.contains_key(url);
+        self.feeds.retain(|feed| is_kept(&feed.url));
+        self.articles.retain(|record| is_kept(&record.feed_url));
+        for feed in &mut self.feeds {
+            if let (Some(base), Some(local)) =
+                (base.feed_map.get(&feed.url), local.feed_map.get(&feed.url))
+            {
+                keep_local_settings(feed, base, local);
+            }
+        }
+        for record in &mut self.articles {
+            let base = base.get_article(&record.feed_url, &record.id);
+            let local = local.get_article(&record.feed_url, &record.id);
+            if let (Some(base), Some(local)) = (base, local) {
+                keep_local_flags(&mut record.article, base, local);
+            }
+        }
+        self
+    }
+
+    fn apply_to(self, state: &mut State) {
+        if let Some(settings) = self.settings {
+            settings.apply(state);
+        }
+        for url in self.removed {
+            state.feed_map.remove(&url);
+        }
+        for feed in self.feeds {
+            let article_map = state
+                .feed_map
+                .remove(&feed.url)
+                .map(|f| f.article_map)
+                .unwrap_or_default();
+            state.feed_map.insert(
+                feed.url.clone(),
+                Feed {
+                    article_map,
+                    ..feed
+                },
+            );
+        }
+        for record in self.articles {
+            if let Some(feed) = state.feed_map.get_mut(&record.feed_url) {
+                feed.article_map.insert(record.id, record.article);
+            }
+        }
+        for (url, id) in self.removed_articles {
+            if let Some(feed) = state.feed_map.get_mut(&url) {
+                feed.article_map.remove(&id);
+            }
+        }
+    }
+}
+
+fn keep_local_settings(feed: &mut Feed, base: &Feed, local: &Feed) {
+    if local.custom_title != base.custom_title {
+        feed.custom_title = local.custom_title.clone();
+    }
+    if local.folder != base.folder {
+        feed.folder = local.folder.clone();
+    }
+    if local.visible != base.visible {
+        feed.visible = local.visible;
+    }
+    if local.refresh_minutes != base.refresh_minutes {
+        feed.refresh_minutes = local.refresh_minutes;
+    }
+    if local.notifications != base.notifications {
+        feed.notifications = local.notifications.clone();
+    }
+}
+
+fn keep_local_flags(article: &mut Article, base: &Article, local: &Article) {
+    if local.read != base.read {
+        article.read = local.read;
+    }
+    if local.starred != base.starred {
+        article.starred = local.starred;
+    }
+}
+
+/// `feed` without its articles, as the feed store keeps it.
+fn metadata(feed: &Feed) -> Feed {
+    Feed {
+        title: feed.title.clone(),
+        custom_title: feed.custom_title.clone(),
+        url: feed.url.clone(),
+        site_url: feed.site_url.clone(),
+        folder: feed.folder.clone(),
+        article_map: HashMap::new(),
+        updated: feed.updated,
+        visible: feed.visible,
+        status: feed.status.clone(),
+        schedule: feed.schedule.clone(),
+        refresh_minutes: feed.refresh_minutes,
+        notifications: feed.notifications.clone(),
+        etag: feed.etag.clone(),
+        last_modified: feed.last_modified.clone(),
+    }
+}
+
+/// An article as the article store keeps it, keyed by `[feed_url, id]`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ArticleRecord {
+    feed_url: String,
+    id: String,
+    article: Article,
+}
+
+/// Opens the database and reads the state from it, first moving over any
+/// state an earlier version left in localStorage.
+pub fn load() -> impl Future<Item = State, Error = ()> {
+    open()
+        .and_then(|db| {
+            DB.with(|cell| *cell.borrow_mut() = Some(db.clone()));
+            match migrate() {
+                Some(state) => Either::A(future::ok(state)),
+                None => Either::B(read(&db)),
+            }
+        })
+        .or_else(|e| {
+            console::log_1(&format!("failed to load state: {:?}", e).into());
+            let mut state = State::default();
+            state.notice = Some("Could not open storage; changes will not be saved".to_string());
+            Ok(state)
+        })
+        .map(|state| {
+            PENDING.with(|pending| pending.borrow_mut().flushed = state.clone());
+            flush_on_pagehide();
+            state
+        })
+}
+
+/// Queues `state` to be written once no change has come for
+/// `SAVE_DELAY_MS`, or at the latest `MAX_SAVE_DELAY_MS` after the first
+/// one. Changes to transient UI alone write nothing.
+pub fn save(old_state: &State, state: &State) {
+    if Settings::of(old_state) == Settings::of(state) && old_state.feed_map == state.feed_map {
+        return;
+    }
+    let now = Date::now();
+    let is_overdue = PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        if pending.latest.replace(state.clone()).is_none() {
+            pending.since = now;
+        }
+        if let Some(timer) = pending.timer.take() {
+            window().unwrap().clear_timeout_with_handle(timer);
+        }
+        if now - pending.since >= MAX_SAVE_DELAY_MS {
+            return true;
+        }
+        let timer = FLUSH.with(|closure| {
+            window()
+                .unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    closure.as_ref().unchecked_ref(),
+                    SAVE_DELAY_MS,
+                )
+                .unwrap()
+        });
+        pending.timer = Some(timer);
+        false
+    });
+    if is_overdue {
+        flush();
+    }
+}
+
+/// Writes the records which changed since the last flush, then tells the
+/// other pages what changed.
+fn flush() {
+    PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        if let Some(timer) = pending.timer.take() {
+            window().unwrap().clear_timeout_with_handle(timer);
+        }
+        let state = match pending.latest.take() {
+            Some(state) => state,
+            None => return,
+        };
+        let change = Change::between(&pending.flushed, &state);
+        let transaction = if change.is_empty() {
+            None
+        } else {
+            write(&change)
+        };
+        if let Some(transaction) = transaction {
+            let message = JsValue::from_serde(&change).unwrap();
+            let closure = Closure::once(move || {
+                CHANNEL.with(|channel| channel.post_message(&message).unwrap());
+            });
+            transaction.set_oncomplete(Some(closure.as_ref().unchecked_ref()));
+            closure.forget();
+        }
+        pending.flushed = state;
+    });
+}
+
+/// Writes what is still waiting when the page closes.
+fn flush_on_pagehide() {
+    FLUSH.with(|closure| {
+        window()
+            .unwrap()
+            .add_event_listener_with_callback("pagehide", closure.as_ref().unchecked_ref())
+            .unwrap();
+    });
+}
+
+/// Passes what other pages store to `dispatch`.
+pub fn listen<F>(dispatch: F)
+where
+    F: Fn(Change) + 'static,
+{
+    let closure = Closure::wrap(Box::new(move |e: MessageEvent| {
+        let change: Change = e.data().into_serde().unwrap();
+        dispatch(PENDING.with(|pending| pending.borrow_mut().sync(change)));
+    }) as Box<FnMut(_)>);
+    CHANNEL.with(|channel| channel.set_onmessage(Some(closure.as_ref().unchecked_ref())));
+    closure.forget();
+}
+
+fn open() -> impl Future<Item = IdbDatabase, Error = JsValue> {
+    let request = window().unwrap().indexed_db().and_then(|factory| {
+        factory
+            .ok_or_else(|| JsValue::from_str("IndexedDB is unavailable"))?
+            .open_with_u32(DB_NAME, DB_VERSION)
+    });
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => return Either::A(future::err(e)),
+    };
+    let request_1 = request.clone();
+    let closure = Closure::once(move || {
+        let db: IdbDatabase = request_1.result().unwrap().unchecked_into();
+        db.create_object_store(SETTINGS_STORE).unwrap();
+        let mut feed_params = IdbObjectStoreParameters::new();
+        feed_params.key_path(Some(&"url".into()));
+        db.create_object_store_with_optional_parameters(FEED_STORE, &feed_params)
+            .unwrap();
+        let mut article_params = IdbObjectStoreParameters::new();
+        article_params.key_path(Some(&JsValue::from_serde(&["feed_url", "id"]).unwrap()));
+        db.create_object_store_with_optional_parameters(ARTICLE_STORE, &article_params)
+            .unwrap();
+    });
+    request.set_onupgradeneeded(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+    Either::B(wait(&request).map(|db| db.unchecked_into()))
+}
+
+fn read(db: &IdbDatabase) -> impl Future<Item = State, Error = JsValue> {
+    let transaction = db.transaction_with_str_sequence(&store_names()).unwrap();
+    let settings = transaction
+        .object_store(SETTINGS_STORE)
+        .unwrap()
+        .get(&SETTINGS_KEY.into())
+        .unwrap();
+    let feeds = transaction
+        .object_store(FEED_STORE)
+        .unwrap()
+        .get_all()
+        .unwrap();
+    let articles = transaction
+        .object_store(ARTICLE_STORE)
+        .unwrap()
+        .get_all()
+        .unwrap();
+    wait(&settings)
+        .join3(wait(&feeds), wait(&articles))
+        .map(|(settings, feeds, articles)| {
+            let mut state = State::default();
+            let mut dropped = 0;
+            if !settings.is_undefined() {
+                match settings.into_serde::<Settings>() {
+                    Ok(settings) => settings.apply(&mut state),
+                    Err(_) => dropped += 1,
+                }
+            }
+            for feed in records::<Feed>(&feeds, &mut dropped) {
+                state.feed_map.insert(feed.url.clone(), feed);
+            }
+            for record in records::<ArticleRecord>(&articles, &mut dropped) {
+                if let Some(feed) = state.feed_map.get_mut(&record.feed_url) {
+                    feed.article_map.insert(record.id, record.article);
+                }
+            }
+            if dropped > 0 {
+                console::log_1(&format!("skipped {} unreadable records", dropped).into());
+                state.notice = Some(format!(
+                    "{} stored records could not be read and were skipped",
+                    dropped
+                ));
+            }
+            state
+        })
+}
+
+/// Reads each record of a `getAll` result on its own, counting the ones
+/// which do not deserialize in `dropped` rather than failing them all.
+fn records<T: DeserializeOwned>(values: &JsValue, dropped: &mut usize) -> Vec<T> {
+    let values: Vec<serde_json::Value> = values.into_serde().unwrap_or_default();
+    values
+        .into_iter()
+        .filter_map(|value| {
+            let record = serde_json::from_value(value).ok();
+            if record.is_none() {
+                *dropped += 1;
+            }
+            record
+        })
+        .collect()
+}
+
+/// Moves the state kept in localStorage by earlier versions into the
+/// database, dropping it from localStorage once written. A blob which does
+/// not parse is left where it is.
+fn migrate() -> Option<State> {
+    let storage = window().unwrap().local_storage().ok()??;
+    let stored = storage.get_item(LEGACY_STATE_KEY).ok()??;
+    let state: State = match serde_json::from_str(&stored) {
+        Ok(state) => state,
+        Err(e) => {
+            console::log_1(&format!("failed to migrate stored state: {}", e).into());
+            return None;
+        }
+    };
+    let mut change = Change::between(&State::default(), &state);
+    change.settings = Some(Settings::of(&state));
+    let transaction = write(&change)?;
+    let closure = Closure::once(move || {
+        storage.remove_item(LEGACY_STATE_KEY).unwrap();
+    });
+    transaction.set_oncomplete(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+    Some(state)
+}
+
+/// Puts `change` in one transaction. Nothing is written if the database
+/// could not be opened.
+fn write(change: &Change) -> Option<IdbTransaction> {
+    let transaction = DB.with(|db| {
+        db.borrow()
+            .as_ref()?
+            .transaction_with_str_sequence_and_mode(&store_names(), IdbTransactionMode::Readwrite)
+            .ok()
+    })?;
+    let settings_store = transaction.object_store(SETTINGS_STORE).unwrap();
+    let feed_store = transaction.object_store(FEED_STORE).unwrap();
+    let article_store = transaction.object_store(ARTICLE_STORE).unwrap();
+
+    if let Some(ref settings) = change.settings {
+        settings_store
+            .put_with_key(&to_js(settings), &SETTINGS_KEY.into())
+            .unwrap();
+    }
+    for feed in &change.feeds {
+        feed_store.put(&to_js(feed)).unwrap();
+    }
+    for record in &change.articles {
+        article_store.put(&to_js(record)).unwrap();
+    }
+    for key in &change.removed_articles {
+        article_store.delete(&to_js(key)).unwrap();
+    }
+    for url in &change.removed {
+        feed_store.delete(&url.as_str().into()).unwrap();
+        // Arrays sort after strings, so this covers every `[url, id]`.
+        let articles =
+            IdbKeyRange::bound(&to_js(&json!([url])), &to_js(&json!([url, []]))).unwrap();
+        article_store.delete(&articles).unwrap();
+    }
+    Some(transaction)
+}
+
+fn store_names() -> JsValue {
+    JsValue::from_serde(STORE_NAMES).unwrap()
+}
+
+fn to_js<T: Serialize>(value: &T) -> JsValue {
+    JsValue::from_serde(value).unwrap()
+}
+
+fn wait(request: &IdbRequest) -> impl Future<Item = JsValue, Error = JsValue> {
+    let p = Promise::new(&mut |resolve, reject| {
+        let request_1 = request.clone();
+        let onsuccess = Closure::once(move || {
+            resolve
+                .call1(&JsValue::null(), &request_1.result().unwrap())
+                .unwrap();
+        });
+        let onerror = Closure::once(move || {
+            reject.call0(&JsValue::null()).unwrap();
+        });
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        onsuccess.forget();
+        onerror.forget();
+    });
+    JsFuture::from(p)
+}
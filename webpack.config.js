@@ -5,10 +5,13 @@ const dist = path.resolve(__dirname, "pkg/dist");
 const WasmPackPlugin = require("@wasm-tool/wasm-pack-plugin");
 
 module.exports = {
-    entry: "./js/index.js",
+    entry: {
+        bundle: "./js/index.js",
+        background: "./js/background.js"
+    },
     output: {
         path: dist,
-        filename: "bundle.js"
+        filename: "[name].js"
     },
     devServer: {
         contentBase: dist,
@@ -28,7 +31,8 @@ module.exports = {
     },
     plugins: [
         new HtmlWebpackPlugin({
-            template: './index.html'
+            template: './index.html',
+            chunks: ['bundle']
         }),
 
         new WasmPackPlugin({